@@ -0,0 +1,36 @@
+//! Bearer token checks shared by the views, the presence list and the event
+//! log.
+
+/// Compare the strings in constant time, to not leak tokens through the
+/// response time.
+pub(crate) fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len() && a.bytes().zip(b.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Return whether the token is one of the allowed tokens. Without allowed
+/// tokens, no token is allowed.
+pub(crate) fn token_allowed(tokens: &[String], token: Option<&str>) -> bool {
+    token.is_some_and(|token| tokens.iter().any(|t| constant_time_eq(t, token)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare() {
+        assert!(constant_time_eq("secret", "secret"));
+        assert!(!constant_time_eq("secret", "secreT"));
+        assert!(!constant_time_eq("secret", "secret2"));
+    }
+
+    #[test]
+    fn allowed_tokens() {
+        let tokens = vec!["a".to_string(), "b".to_string()];
+        assert!(token_allowed(&tokens, Some("b")));
+        assert!(!token_allowed(&tokens, Some("c")));
+        assert!(!token_allowed(&tokens, None));
+        assert!(!token_allowed(&[], Some("")));
+        assert!(!token_allowed(&[], None));
+    }
+}
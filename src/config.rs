@@ -0,0 +1,847 @@
+//! Configuration file support.
+//!
+//! This module is only available with the `config` feature. It is used by the
+//! `spaceapi-server` binary, which lets you run a SpaceAPI server without
+//! writing any Rust code.
+//!
+//! A config file is written in [TOML](https://toml.io/) and looks like this:
+//!
+//! ```toml
+//! # Address to listen on. On Unix, use e.g. "unix:/run/spaceapi.sock" to
+//! # listen on a Unix domain socket instead.
+//! listen = "127.0.0.1:8000"
+//!
+//! # Optional, cache the rendered status for the specified number of seconds
+//! response_cache_ttl = 5
+//!
+//! # Optional, the locale of the untranslated messages, defaults to "en"
+//! default_locale = "en"
+//!
+//! [store]
+//! redis_url = "redis://127.0.0.1/"
+//!
+//! # The static status data, using the field names of the SpaceAPI JSON format
+//! [status]
+//! api_compatibility = ["14"]
+//! space = "coredump"
+//! logo = "https://www.coredump.ch/logo.png"
+//! url = "https://www.coredump.ch/"
+//! location = { address = "Spinnereistrasse 2, 8640 Rapperswil, Switzerland", lat = 47.22936, lon = 8.82949 }
+//! contact = { twitter = "@coredump_ch" }
+//! state = { open = false }
+//!
+//! # Sensors, the values are read from the specified Redis key
+//! [[sensors]]
+//! kind = "people_now_present"
+//! data_key = "people_now_present"
+//! location = "Hackerspace"
+//!
+//! [[sensors]]
+//! kind = "temperature"
+//! data_key = "temp_room1"
+//! location = "Room 1"
+//! unit = "°C"
+//!
+//! # Status modifiers, applied in the specified order
+//! [[modifiers]]
+//! kind = "state_from_people_now_present"
+//! # Optional, the message with plural forms and translations, see
+//! # `modifiers::Message`. `{count}` is replaced with the number of people.
+//! message = { other = "{count} people here", one = "{count} person here", translations = { de = { other = "{count} Personen anwesend", one = "Eine Person anwesend" } } }
+//!
+//! # Open while a door is unlocked, see `modifiers::StateFromDoorLocked`
+//! [[modifiers]]
+//! kind = "state_from_door_locked"
+//! # Optional, "first" (default), "any" or "all" door_locked sensors decide
+//! doors = "any"
+//! # Optional, use the door_locked sensor with this name instead
+//! # sensor = "Front door"
+//! open_message = "Come in!"
+//! closed_message = "All doors are locked"
+//!
+//! # Open by schedule, see `modifiers::OpeningHours`
+//! [[modifiers]]
+//! kind = "opening_hours"
+//! timezone = "Europe/Zurich"
+//! # Optional, one of "either" (default), "schedule" or "sensors"
+//! precedence = "either"
+//! schedule = [
+//!     { days = ["tue", "fri"], from = "18:00", until = "23:00" },
+//! ]
+//! holidays = [
+//!     { date = "2024-12-24", name = "Christmas Eve" },
+//! ]
+//! # Optional, `{until}` is replaced with the closing time
+//! open_message = "Open until {until}"
+//! # Optional, `{holiday}` is replaced with the name of the holiday
+//! holiday_message = { other = "Closed: {holiday}", translations = { de = "Geschlossen: {holiday}" } }
+//!
+//! # Serve the 10 most recent events of the event log, see `events`
+//! [[modifiers]]
+//! kind = "recent_events"
+//! count = 10
+//!
+//! # Optional, let members check in and out, see `presence`
+//! [presence]
+//! # Optional, check out automatically after this number of seconds
+//! checkout_after = 43200
+//!
+//! # The members who may check in, with the bearer token each one sends
+//! [presence.members]
+//! alice = "alice-token"
+//! bob = "bob-token"
+//!
+//! # Optional, record events like state changes and check-ins, see `events`
+//! [events]
+//! # Optional, keep at most this number of events
+//! max_len = 1000
+//! # Optional, record the updates of the sensors with these data keys
+//! sensors = ["people_now_present"]
+//! # Optional, the bearer tokens allowed to post events. Without tokens,
+//! # nobody can post events.
+//! tokens = ["doorbell-token"]
+//!
+//! # Views, tried in the specified order, see `views::View`. The last one
+//! # applies to all other clients.
+//! [[views]]
+//! name = "internal"
+//! networks = ["192.168.42.0/24", "fd00::/8"]
+//! tokens = ["secret-token"]
+//!
+//! [[views]]
+//! name = "public"
+//! hide = ["sensors.people_now_present.names", "sensors.power_consumption"]
+//! ```
+//!
+//! Messages are either plain strings or tables with plural forms and
+//! translations. Translations are served to clients that prefer the language
+//! in their `Accept-Language` header.
+//!
+//! The following sensor kinds are supported: `account_balance`, `barometer`,
+//! `beverage_supply`, `door_locked`, `humidity`, `people_now_present`,
+//! `power_consumption`, `temperature` and `total_member_count`. Apart from
+//! `kind` and `data_key`, each sensor takes the optional `name`, `location`
+//! and `description` fields. Some sensor kinds additionally require a `unit`
+//! or a `location`.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::api;
+use crate::api::sensors::{self, SensorMetadata, SensorMetadataWithLocation};
+use crate::errors::SpaceapiServerError;
+use crate::events::EventLog;
+use crate::modifiers::{self, Message};
+use crate::presence::Presence;
+use crate::server::SpaceapiServerBuilder;
+use crate::views;
+
+fn default_listen() -> String {
+    "127.0.0.1:8000".into()
+}
+
+/// The contents of a config file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    /// The address to listen on, defaults to `127.0.0.1:8000`. A value of
+    /// the form `unix:<path>` listens on a Unix domain socket.
+    #[serde(default = "default_listen")]
+    pub listen: String,
+    /// Cache the rendered status for this number of seconds.
+    #[serde(default)]
+    pub response_cache_ttl: Option<u64>,
+    /// The locale of the untranslated messages, defaults to `en`.
+    #[serde(default)]
+    pub default_locale: Option<String>,
+    /// The sensor store.
+    pub store: StoreConfig,
+    /// The static status data.
+    pub status: api::Status,
+    /// The registered sensors.
+    #[serde(default)]
+    pub sensors: Vec<SensorConfig>,
+    /// The status modifiers, in the order they are applied.
+    #[serde(default)]
+    pub modifiers: Vec<ModifierConfig>,
+    /// The views of the status, in the order they are tried.
+    #[serde(default)]
+    pub views: Vec<ViewConfig>,
+    /// Let members check in and out.
+    pub presence: Option<PresenceConfig>,
+    /// Record events.
+    pub events: Option<EventsConfig>,
+}
+
+/// Configuration of the sensor store.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StoreConfig {
+    /// A Redis connection string, e.g. `redis://127.0.0.1/`.
+    pub redis_url: String,
+}
+
+/// The kind of a sensor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[allow(missing_docs)]
+pub enum SensorKind {
+    AccountBalance,
+    Barometer,
+    BeverageSupply,
+    DoorLocked,
+    Humidity,
+    PeopleNowPresent,
+    PowerConsumption,
+    Temperature,
+    TotalMemberCount,
+}
+
+/// Configuration of a single sensor.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SensorConfig {
+    /// The kind of sensor.
+    pub kind: SensorKind,
+    /// The key that is used to store and update the sensor value.
+    pub data_key: String,
+    /// The sensor name.
+    #[serde(default)]
+    pub name: Option<String>,
+    /// The sensor location. Required for some sensor kinds.
+    #[serde(default)]
+    pub location: Option<String>,
+    /// The sensor description.
+    #[serde(default)]
+    pub description: Option<String>,
+    /// The unit of the sensor value. Required for some sensor kinds.
+    #[serde(default)]
+    pub unit: Option<String>,
+}
+
+impl SensorConfig {
+    fn metadata(&self) -> SensorMetadata {
+        SensorMetadata {
+            name: self.name.clone(),
+            location: self.location.clone(),
+            description: self.description.clone(),
+        }
+    }
+
+    fn metadata_with_location(&self) -> Result<SensorMetadataWithLocation, SpaceapiServerError> {
+        Ok(SensorMetadataWithLocation {
+            name: self.name.clone(),
+            location: self.required(&self.location, "location")?,
+            description: self.description.clone(),
+        })
+    }
+
+    fn unit(&self) -> Result<String, SpaceapiServerError> {
+        self.required(&self.unit, "unit")
+    }
+
+    fn required(&self, value: &Option<String>, field: &str) -> Result<String, SpaceapiServerError> {
+        value.clone().ok_or_else(|| {
+            format!(
+                "Sensor \"{}\" of kind {:?} requires a {}",
+                self.data_key, self.kind, field
+            )
+            .into()
+        })
+    }
+
+    /// Register the sensor with the specified builder.
+    fn register(&self, builder: SpaceapiServerBuilder) -> Result<SpaceapiServerBuilder, SpaceapiServerError> {
+        let data_key = self.data_key.clone();
+        Ok(match self.kind {
+            SensorKind::AccountBalance => builder.add_sensor(
+                sensors::AccountBalanceSensorTemplate {
+                    metadata: self.metadata(),
+                    unit: self.unit()?,
+                },
+                data_key,
+            ),
+            SensorKind::Barometer => builder.add_sensor(
+                sensors::BarometerSensorTemplate {
+                    metadata: self.metadata_with_location()?,
+                    unit: self.unit()?,
+                },
+                data_key,
+            ),
+            SensorKind::BeverageSupply => builder.add_sensor(
+                sensors::BeverageSupplySensorTemplate {
+                    metadata: self.metadata(),
+                    unit: self.unit()?,
+                },
+                data_key,
+            ),
+            SensorKind::DoorLocked => builder.add_sensor(
+                sensors::DoorLockedSensorTemplate {
+                    metadata: self.metadata_with_location()?,
+                },
+                data_key,
+            ),
+            SensorKind::Humidity => builder.add_sensor(
+                sensors::HumiditySensorTemplate {
+                    metadata: self.metadata_with_location()?,
+                    unit: self.unit()?,
+                },
+                data_key,
+            ),
+            SensorKind::PeopleNowPresent => builder.add_sensor(
+                sensors::PeopleNowPresentSensorTemplate {
+                    metadata: self.metadata(),
+                },
+                data_key,
+            ),
+            SensorKind::PowerConsumption => builder.add_sensor(
+                sensors::PowerConsumptionSensorTemplate {
+                    metadata: self.metadata_with_location()?,
+                    unit: self.unit()?,
+                },
+                data_key,
+            ),
+            SensorKind::Temperature => builder.add_sensor(
+                sensors::TemperatureSensorTemplate {
+                    metadata: self.metadata_with_location()?,
+                    unit: self.unit()?,
+                },
+                data_key,
+            ),
+            SensorKind::TotalMemberCount => builder.add_sensor(
+                sensors::TotalMemberCountSensorTemplate {
+                    metadata: self.metadata(),
+                },
+                data_key,
+            ),
+        })
+    }
+}
+
+/// Regular opening hours of an [`OpeningHours`](../modifiers/struct.OpeningHours.html)
+/// modifier.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OpeningConfig {
+    /// The days of the week, e.g. `tue` or `tuesday`.
+    pub days: Vec<String>,
+    /// The opening time, e.g. `18:00`.
+    pub from: String,
+    /// The closing time, e.g. `23:00`. May be on the next day.
+    pub until: String,
+}
+
+/// A holiday of an [`OpeningHours`](../modifiers/struct.OpeningHours.html)
+/// modifier.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HolidayConfig {
+    /// The date, e.g. `2024-12-24`.
+    pub date: String,
+    /// The name of the holiday.
+    pub name: String,
+}
+
+/// Configuration of the [presence list](../presence/index.html).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PresenceConfig {
+    /// The key of the hash holding the presence list, defaults to `presence`.
+    pub key: Option<String>,
+    /// Check out members automatically after this number of seconds,
+    /// defaults to 12 hours.
+    pub checkout_after: Option<u64>,
+    /// The members who may check in and out, with their bearer tokens.
+    #[serde(default)]
+    pub members: BTreeMap<String, String>,
+}
+
+impl PresenceConfig {
+    fn presence(&self) -> Presence {
+        let mut presence = Presence::new();
+        if let Some(key) = &self.key {
+            presence = presence.key(&**key);
+        }
+        if let Some(checkout_after) = self.checkout_after {
+            presence = presence.checkout_after(Duration::from_secs(checkout_after));
+        }
+        for (name, token) in &self.members {
+            presence = presence.allow_member(&**name, &**token);
+        }
+        presence
+    }
+}
+
+/// Configuration of the [event log](../events/index.html).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EventsConfig {
+    /// The key of the list holding the events, defaults to `events`.
+    pub key: Option<String>,
+    /// Keep at most this number of events, defaults to 100. With 0, all
+    /// events are kept.
+    pub max_len: Option<usize>,
+    /// Record the updates of the sensors with these data keys.
+    #[serde(default)]
+    pub sensors: Vec<String>,
+    /// Only clients sending one of these bearer tokens may post events.
+    /// Without tokens, nobody can post events.
+    #[serde(default)]
+    pub tokens: Vec<String>,
+}
+
+impl EventsConfig {
+    fn event_log(&self) -> EventLog {
+        let mut event_log = EventLog::new();
+        if let Some(key) = &self.key {
+            event_log = event_log.key(&**key);
+        }
+        if let Some(max_len) = self.max_len {
+            event_log = event_log.max_len(max_len);
+        }
+        for sensor in &self.sensors {
+            event_log = event_log.record_sensor(&**sensor);
+        }
+        for token in &self.tokens {
+            event_log = event_log.allow_token(&**token);
+        }
+        event_log
+    }
+}
+
+/// Configuration of a [`View`](../views/struct.View.html).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ViewConfig {
+    /// The name of the view.
+    pub name: String,
+    /// Apply the view to clients from these networks, e.g. `10.0.0.0/8`.
+    #[serde(default)]
+    pub networks: Vec<String>,
+    /// Apply the view to clients sending one of these bearer tokens.
+    #[serde(default)]
+    pub tokens: Vec<String>,
+    /// The paths of the fields to hide, e.g. `sensors.people_now_present.names`.
+    #[serde(default)]
+    pub hide: Vec<String>,
+}
+
+impl ViewConfig {
+    fn view(&self) -> Result<views::View, SpaceapiServerError> {
+        let mut view = views::View::new(&*self.name);
+        for network in &self.networks {
+            view = view.allow_network(network.parse()?);
+        }
+        for token in &self.tokens {
+            view = view.allow_token(&**token);
+        }
+        for path in &self.hide {
+            view = view.hide(&**path);
+        }
+        Ok(view)
+    }
+}
+
+/// Which `door_locked` sensors decide the opening state in a
+/// [`StateFromDoorLocked`](../modifiers/struct.StateFromDoorLocked.html)
+/// modifier.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DoorsConfig {
+    /// The first sensor.
+    #[default]
+    First,
+    /// Open if any door is unlocked.
+    Any,
+    /// Open only if all doors are unlocked.
+    All,
+}
+
+/// Configuration of a status modifier.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ModifierConfig {
+    /// See [`modifiers::StateFromPeopleNowPresent`](../modifiers/struct.StateFromPeopleNowPresent.html).
+    StateFromPeopleNowPresent {
+        /// The message while people are present.
+        message: Option<Message>,
+    },
+    /// See [`modifiers::StateFromDoorLocked`](../modifiers/struct.StateFromDoorLocked.html).
+    StateFromDoorLocked {
+        /// Which sensors decide the opening state.
+        #[serde(default)]
+        doors: DoorsConfig,
+        /// Use the first sensor with this name.
+        sensor: Option<String>,
+        /// The message while the space is open.
+        open_message: Option<Message>,
+        /// The message while the space is closed.
+        closed_message: Option<Message>,
+    },
+    /// See [`modifiers::OpeningHours`](../modifiers/struct.OpeningHours.html).
+    OpeningHours {
+        /// The timezone of the schedule, e.g. `Europe/Zurich`.
+        timezone: String,
+        /// How the schedule is combined with a state set by sensors.
+        #[serde(default)]
+        precedence: modifiers::Precedence,
+        /// The weekly opening hours.
+        schedule: Vec<OpeningConfig>,
+        /// Days on which the space is closed.
+        #[serde(default)]
+        holidays: Vec<HolidayConfig>,
+        /// The message while the space is open according to the schedule.
+        open_message: Option<Message>,
+        /// The message on holidays.
+        holiday_message: Option<Message>,
+    },
+    /// See [`modifiers::RecentEvents`](../modifiers/struct.RecentEvents.html).
+    RecentEvents {
+        /// The number of events to serve.
+        count: usize,
+        /// The key of the list holding the events, defaults to `events`.
+        key: Option<String>,
+    },
+}
+
+fn parse<T: FromStr>(value: &str, what: &str) -> Result<T, SpaceapiServerError> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid {}: {}", what, value).into())
+}
+
+impl ModifierConfig {
+    /// Register the modifier with the specified builder.
+    fn register(&self, builder: SpaceapiServerBuilder) -> Result<SpaceapiServerBuilder, SpaceapiServerError> {
+        Ok(match self {
+            ModifierConfig::StateFromPeopleNowPresent { message } => match message {
+                Some(message) => builder.add_status_modifier(
+                    modifiers::StateFromPeopleNowPresent::with_message(message.clone()),
+                ),
+                None => builder.add_status_modifier(modifiers::StateFromPeopleNowPresent),
+            },
+            ModifierConfig::StateFromDoorLocked {
+                doors,
+                sensor,
+                open_message,
+                closed_message,
+            } => {
+                let doors = match (doors, sensor) {
+                    (DoorsConfig::First, Some(name)) => modifiers::Doors::Named(name.clone()),
+                    (DoorsConfig::First, None) => modifiers::Doors::First,
+                    (_, Some(_)) => return Err("The sensor and doors settings cannot be combined".into()),
+                    (DoorsConfig::Any, None) => modifiers::Doors::Any,
+                    (DoorsConfig::All, None) => modifiers::Doors::All,
+                };
+                let mut modifier = modifiers::StateFromDoorLocked::default().doors(doors);
+                if let Some(message) = open_message {
+                    modifier = modifier.open_message(message.clone());
+                }
+                if let Some(message) = closed_message {
+                    modifier = modifier.closed_message(message.clone());
+                }
+                builder.add_status_modifier(modifier)
+            }
+            ModifierConfig::OpeningHours {
+                timezone,
+                precedence,
+                schedule,
+                holidays,
+                open_message,
+                holiday_message,
+            } => {
+                let mut opening_hours =
+                    modifiers::OpeningHours::new(parse(timezone, "timezone")?).precedence(*precedence);
+                for opening in schedule {
+                    let time = |time: &str| {
+                        modifiers::NaiveTime::parse_from_str(time, "%H:%M")
+                            .map_err(|_| format!("Invalid time: {}", time))
+                    };
+                    let (from, until) = (time(&opening.from)?, time(&opening.until)?);
+                    for day in &opening.days {
+                        opening_hours = opening_hours.open(parse(day, "day")?, from, until);
+                    }
+                }
+                for holiday in holidays {
+                    opening_hours = opening_hours.holiday(parse(&holiday.date, "date")?, &*holiday.name);
+                }
+                if let Some(message) = open_message {
+                    opening_hours = opening_hours.open_message(message.clone());
+                }
+                if let Some(message) = holiday_message {
+                    opening_hours = opening_hours.holiday_message(message.clone());
+                }
+                builder.add_contextual_status_modifier(opening_hours)
+            }
+            ModifierConfig::RecentEvents { count, key } => {
+                let mut modifier = modifiers::RecentEvents::new(*count);
+                if let Some(key) = key {
+                    modifier = modifier.key(&**key);
+                }
+                builder.add_contextual_status_modifier(modifier)
+            }
+        })
+    }
+}
+
+impl Config {
+    /// Parse a config from a TOML string.
+    pub fn parse(config: &str) -> Result<Config, SpaceapiServerError> {
+        toml::from_str(config).map_err(|e| format!("Invalid config: {}", e).into())
+    }
+
+    /// Read and parse a config file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Config, SpaceapiServerError> {
+        let path = path.as_ref();
+        let config = fs::read_to_string(path)
+            .map_err(|e| format!("Could not read config file {}: {}", path.display(), e))?;
+        toml::from_str(&config).map_err(|e| format!("Invalid config file {}: {}", path.display(), e).into())
+    }
+
+    /// Create a server builder with the configured status, store, sensors
+    /// and modifiers.
+    pub fn server_builder(&self) -> Result<SpaceapiServerBuilder, SpaceapiServerError> {
+        let mut builder =
+            SpaceapiServerBuilder::new(self.status.clone()).redis_connection_info(&*self.store.redis_url);
+        if let Some(ttl) = self.response_cache_ttl {
+            builder = builder.response_cache_ttl(Duration::from_secs(ttl));
+        }
+        if let Some(locale) = &self.default_locale {
+            builder = builder.default_locale(&**locale);
+        }
+        for sensor in &self.sensors {
+            builder = sensor.register(builder)?;
+        }
+        for modifier in &self.modifiers {
+            builder = modifier.register(builder)?;
+        }
+        for view in &self.views {
+            builder = builder.add_view(view.view()?);
+        }
+        if let Some(presence) = &self.presence {
+            builder = builder.presence(presence.presence());
+        }
+        if let Some(events) = &self.events {
+            builder = builder.event_log(events.event_log());
+        }
+        Ok(builder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+        listen = "0.0.0.0:8080"
+
+        [store]
+        redis_url = "redis://127.0.0.1/"
+
+        [status]
+        api_compatibility = ["14"]
+        space = "ourspace"
+        logo = "https://example.com/logo.png"
+        url = "https://example.com/"
+        location = { lat = 47.123, lon = 8.88 }
+        contact = { email = "hi@example.com" }
+
+        [[sensors]]
+        kind = "temperature"
+        data_key = "temp_room1"
+        location = "Room 1"
+        unit = "°C"
+
+        [[modifiers]]
+        kind = "state_from_people_now_present"
+    "#;
+
+    #[test]
+    fn parse_config() {
+        let config = Config::parse(CONFIG).unwrap();
+        assert_eq!(config.listen, "0.0.0.0:8080");
+        assert_eq!(config.response_cache_ttl, None);
+        assert_eq!(config.status.space, "ourspace");
+        assert_eq!(config.status.contact.email, Some("hi@example.com".into()));
+        assert_eq!(config.sensors.len(), 1);
+        assert_eq!(config.sensors[0].kind, SensorKind::Temperature);
+        assert_eq!(config.modifiers.len(), 1);
+        assert!(config.server_builder().unwrap().build().is_ok());
+    }
+
+    #[test]
+    fn default_listen_address() {
+        let config = Config::parse(&CONFIG.replace("listen = \"0.0.0.0:8080\"", "")).unwrap();
+        assert_eq!(config.listen, "127.0.0.1:8000");
+    }
+
+    #[test]
+    fn missing_unit() {
+        let config = Config::parse(&CONFIG.replace("unit = \"°C\"", "")).unwrap();
+        let err = config.server_builder().err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Sensor \"temp_room1\" of kind Temperature requires a unit"
+        );
+    }
+
+    #[test]
+    fn state_from_door_locked() {
+        let modifier = r#"
+            [[modifiers]]
+            kind = "state_from_door_locked"
+            doors = "all"
+            open_message = "Come in"
+        "#;
+        let config = Config::parse(&format!("{}{}", CONFIG, modifier)).unwrap();
+        assert!(config.server_builder().is_ok());
+
+        let modifier = modifier.replace("open_message", "sensor = \"Front door\"\nopen_message");
+        let config = Config::parse(&format!("{}{}", CONFIG, modifier)).unwrap();
+        let err = config.server_builder().err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "The sensor and doors settings cannot be combined"
+        );
+    }
+
+    #[test]
+    fn opening_hours() {
+        let modifier = r#"
+            [[modifiers]]
+            kind = "opening_hours"
+            timezone = "Europe/Zurich"
+            precedence = "schedule"
+            schedule = [{ days = ["tue", "friday"], from = "18:00", until = "02:00" }]
+            holidays = [{ date = "2024-12-24", name = "Christmas Eve" }]
+        "#;
+        let config = Config::parse(&format!("{}{}", CONFIG, modifier)).unwrap();
+        assert_eq!(config.modifiers.len(), 2);
+        assert!(config.server_builder().is_ok());
+
+        let config = Config::parse(&format!("{}{}", CONFIG, modifier.replace("18:00", "6pm"))).unwrap();
+        let err = config.server_builder().err().unwrap();
+        assert_eq!(err.to_string(), "Invalid time: 6pm");
+
+        let config = Config::parse(&format!("{}{}", CONFIG, modifier.replace("Zurich", "Atlantis"))).unwrap();
+        let err = config.server_builder().err().unwrap();
+        assert_eq!(err.to_string(), "Invalid timezone: Europe/Atlantis");
+    }
+
+    #[test]
+    fn messages() {
+        let modifier = r#"
+            message = { other = "{count} people", one = "Someone", translations = { de = "{count} Leute" } }
+        "#;
+        let config = Config::parse(&format!("{}{}", CONFIG, modifier)).unwrap();
+        match &config.modifiers[0] {
+            ModifierConfig::StateFromPeopleNowPresent { message } => assert_eq!(
+                message.as_ref(),
+                Some(
+                    &Message::new("{count} people")
+                        .one("Someone")
+                        .translation("de", Message::new("{count} Leute"))
+                )
+            ),
+            modifier => panic!("Unexpected modifier: {:?}", modifier),
+        }
+        assert!(config.server_builder().is_ok());
+
+        let config = Config::parse(&format!("{}{}", CONFIG, modifier.replace("other", "many")));
+        assert!(config.is_err());
+    }
+
+    #[test]
+    fn views() {
+        let views = r#"
+            [[views]]
+            name = "internal"
+            networks = ["10.0.0.0/8"]
+
+            [[views]]
+            name = "public"
+            hide = ["sensors"]
+        "#;
+        let config = Config::parse(&format!("{}{}", CONFIG, views)).unwrap();
+        assert_eq!(config.views.len(), 2);
+        assert_eq!(config.views[1].hide, vec!["sensors"]);
+        assert!(config.server_builder().is_ok());
+
+        let config = Config::parse(&format!("{}{}", CONFIG, views.replace("/8", "/64"))).unwrap();
+        let err = config.server_builder().err().unwrap();
+        assert_eq!(err.to_string(), "Invalid network: 10.0.0.0/64");
+
+        // The last view applies to all other clients
+        let views = r#"
+            [[views]]
+            name = "internal"
+            networks = ["10.0.0.0/8"]
+        "#;
+        let config = Config::parse(&format!("{}{}", CONFIG, views)).unwrap();
+        let err = config.server_builder().unwrap().build().err().unwrap();
+        assert!(err
+            .to_string()
+            .starts_with("The last view \"internal\" must apply to all clients"));
+    }
+
+    #[test]
+    fn presence() {
+        let presence = r#"
+            [presence]
+            checkout_after = 3600
+
+            [presence.members]
+            alice = "alice-token"
+            bob = "bob-token"
+        "#;
+        let config = Config::parse(&format!("{}{}", CONFIG, presence)).unwrap();
+        assert_eq!(
+            config.presence.unwrap().presence(),
+            Presence::new()
+                .checkout_after(Duration::from_secs(3600))
+                .allow_member("alice", "alice-token")
+                .allow_member("bob", "bob-token")
+        );
+    }
+
+    #[test]
+    fn events() {
+        let events = r#"
+            [[modifiers]]
+            kind = "recent_events"
+            count = 5
+            key = "log"
+
+            [events]
+            key = "log"
+            max_len = 50
+            sensors = ["temp_room1"]
+        "#;
+        let config = Config::parse(&format!("{}{}", CONFIG, events)).unwrap();
+        match &config.modifiers[1] {
+            ModifierConfig::RecentEvents { count, key } => {
+                assert_eq!(*count, 5);
+                assert_eq!(key.as_deref(), Some("log"));
+            }
+            modifier => panic!("Unexpected modifier: {:?}", modifier),
+        }
+        let events = config.events.as_ref().unwrap();
+        assert_eq!(events.max_len, Some(50));
+        assert!(events.tokens.is_empty());
+        let event_log = events.event_log();
+        assert!(event_log.records_sensor("temp_room1"));
+        assert!(!event_log.allows(None));
+        assert!(config.server_builder().is_ok());
+    }
+
+    #[test]
+    fn unknown_sensor_kind() {
+        let err = Config::parse(&CONFIG.replace("\"temperature\"", "\"flux_capacitor\"")).unwrap_err();
+        assert!(err.to_string().starts_with("Invalid config: "));
+    }
+}
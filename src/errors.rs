@@ -7,6 +7,8 @@ use quick_error::quick_error;
 use r2d2::Error as R2d2Error;
 use redis::RedisError;
 
+use crate::store::StoreError;
+
 quick_error! {
     /// A ``SpaceapiServerError`` wraps general problems that can occur in the SpaceAPI server.
     #[derive(Debug)]
@@ -21,11 +23,21 @@ quick_error! {
             from()
             source(err)
         }
+        /// A problem with the store occurred.
+        Store(err: StoreError) {
+            from()
+            display("{}", err)
+            source(err)
+        }
         /// An I/O error occurred.
         IoError(err: io::Error) {
             from()
             source(err)
         }
+        /// The status does not match the SpaceAPI schema.
+        InvalidStatus(err: String) {
+            display("Invalid status: {}", err)
+        }
         /// Another error happened.
         Message(err: Cow<'static, str>) {
             display("{}", err)
@@ -0,0 +1,297 @@
+//! An append-only log of events, served in the `events` field of the status.
+//!
+//! With an event log, the server records an event whenever
+//!
+//! - the value of a sensor chosen with
+//!   [`record_sensor`](struct.EventLog.html#method.record_sensor) is updated
+//!   (type `sensor-update`, named after the data key, with the new value as
+//!   `extra`),
+//! - a sensor update through the HTTP API opens or closes the space (type
+//!   `open` or `closed`, named `state`), as far as the sensors and the
+//!   [cacheable](../modifiers/trait.ContextualStatusModifier.html#method.cacheable)
+//!   status modifiers tell,
+//! - a member checks in or out with a visible name (type `check-in` or
+//!   `check-out`, named after the member).
+//!
+//! Other events, e.g. from a door bell, are recorded with a `POST` request to
+//! `/events`, with the `name`, `type` and optional `extra` parameters and one
+//! of the tokens allowed with
+//! [`allow_token`](struct.EventLog.html#method.allow_token):
+//!
+//! ```text
+//! curl -X POST -H "Authorization: Bearer doorbell-token" -d name=doorbell -d type=ring http://127.0.0.1:8000/events
+//! ```
+//!
+//! The log doesn't change the status by itself. Add the
+//! [`RecentEvents`](../modifiers/struct.RecentEvents.html) modifier to serve
+//! the most recent events:
+//!
+//! ```
+//! use spaceapi_server::events::EventLog;
+//! use spaceapi_server::modifiers::RecentEvents;
+//!
+//! let event_log = EventLog::new()
+//!     .max_len(1000)
+//!     .record_sensor("door_locked")
+//!     .allow_token("doorbell-token");
+//! let modifier = RecentEvents::new(10);
+//! ```
+//!
+//! The log is kept in a list in the store, newest event first, so the store
+//! must support lists, like Redis does. The last state of the space is kept
+//! next to it, at the key of the list with a `:state` suffix, and swapped
+//! atomically with [`Store::swap_value`](../store/trait.Store.html#method.swap_value).
+
+use std::time::SystemTime;
+
+use log::warn;
+
+use crate::api;
+use crate::auth;
+use crate::store::{self, Store, StoreError};
+
+/// The event log settings, see the [module documentation](index.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EventLog {
+    key: String,
+    max_len: usize,
+    sensors: Vec<String>,
+    tokens: Vec<String>,
+}
+
+impl Default for EventLog {
+    fn default() -> Self {
+        EventLog {
+            key: "events".into(),
+            max_len: 100,
+            sensors: vec![],
+            tokens: vec![],
+        }
+    }
+}
+
+impl EventLog {
+    /// Create the event log settings with the default store key `events`,
+    /// keeping the 100 most recent events.
+    pub fn new() -> EventLog {
+        EventLog::default()
+    }
+
+    /// Set the key of the list holding the events in the store.
+    pub fn key<S: Into<String>>(mut self, key: S) -> Self {
+        self.key = key.into();
+        self
+    }
+
+    /// Keep at most the specified number of events, dropping the oldest
+    /// ones. With 0, all events are kept.
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /// Record the updates of the sensor with the specified data key. Updates
+    /// of other sensors aren't recorded, so that frequently updated sensors
+    /// don't push the other events out of the log.
+    pub fn record_sensor<S: Into<String>>(mut self, data_key: S) -> Self {
+        self.sensors.push(data_key.into());
+        self
+    }
+
+    /// Allow clients that send the specified token in an
+    /// `Authorization: Bearer <token>` header to post events. Without
+    /// tokens, nobody can post events.
+    pub fn allow_token<S: Into<String>>(mut self, token: S) -> Self {
+        self.tokens.push(token.into());
+        self
+    }
+
+    /// Return whether a client with the specified bearer token may post
+    /// events.
+    pub(crate) fn allows(&self, token: Option<&str>) -> bool {
+        auth::token_allowed(&self.tokens, token)
+    }
+
+    /// Return whether the updates of the sensor are recorded.
+    pub(crate) fn records_sensor(&self, data_key: &str) -> bool {
+        self.sensors.iter().any(|sensor| sensor == data_key)
+    }
+
+    /// The key of the last observed state of the space in the store.
+    fn state_key(&self) -> String {
+        format!("{}:state", self.key)
+    }
+
+    /// Append an event to the log.
+    pub(crate) async fn record(
+        &self,
+        store: &dyn Store,
+        name: &str,
+        type_: &str,
+        extra: Option<&str>,
+        now: SystemTime,
+    ) -> Result<(), StoreError> {
+        let event = api::Event {
+            name: name.into(),
+            type_: type_.into(),
+            timestamp: store::unix_time(now),
+            extra: extra.map(Into::into),
+        };
+        let value = serde_json::to_string(&event).expect("Could not serialize event");
+        store.push_to_list(&self.key, &value, self.max_len).await
+    }
+
+    /// Record an event if the space opened or closed since the last observed
+    /// state. The state is swapped atomically in the store, so that each
+    /// change is recorded once, even with concurrent updates. The first
+    /// observed state doesn't count as a change.
+    pub(crate) async fn observe_state(
+        &self,
+        store: &dyn Store,
+        open: Option<bool>,
+        now: SystemTime,
+    ) -> Result<(), StoreError> {
+        let value = match open {
+            Some(true) => "open",
+            Some(false) => "closed",
+            None => "unknown",
+        };
+        let previous = store.swap_value(&self.state_key(), value).await?;
+        match (previous.as_deref(), open) {
+            (Some("closed" | "unknown"), Some(true)) => self.record(store, "state", "open", None, now).await,
+            (Some("open" | "unknown"), Some(false)) => self.record(store, "state", "closed", None, now).await,
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Return the most recent events of the log at the specified key, newest
+/// first. Invalid entries are skipped.
+pub(crate) async fn recent(
+    store: &dyn Store,
+    key: &str,
+    count: usize,
+) -> Result<Vec<api::Event>, StoreError> {
+    Ok(store
+        .get_list(key, count)
+        .await?
+        .into_iter()
+        .filter_map(|value| match serde_json::from_str(&value) {
+            Ok(event) => Some(event),
+            Err(e) => {
+                warn!("Skipping invalid event {:?}: {}", value, e);
+                None
+            }
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::*;
+    use crate::store::MemoryStore;
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn types(events: &[api::Event]) -> Vec<&str> {
+        events.iter().map(|event| event.type_.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn record_and_trim() {
+        let (log, store) = (EventLog::new().max_len(3), MemoryStore::new());
+        log.record(&store, "alice", "check-in", None, at(1))
+            .await
+            .unwrap();
+        store.push_to_list("events", "invalid", 3).await.unwrap();
+        log.record(&store, "temp", "sensor-update", Some("21.5"), at(2))
+            .await
+            .unwrap();
+        log.record(&store, "alice", "check-out", None, at(3))
+            .await
+            .unwrap();
+
+        let events = recent(&store, "events", 10).await.unwrap();
+        assert_eq!(types(&events), vec!["check-out", "sensor-update"]);
+        assert_eq!(events[0].timestamp, 3);
+        assert_eq!(events[1].extra.as_deref(), Some("21.5"));
+        assert_eq!(recent(&store, "events", 1).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn state_changes() {
+        let (log, store) = (EventLog::new(), MemoryStore::new());
+        for (secs, open) in [
+            (1, Some(true)),
+            (2, Some(true)),
+            (3, None),
+            (4, Some(false)),
+            (5, Some(true)),
+        ] {
+            log.observe_state(&store, open, at(secs)).await.unwrap();
+        }
+        let events = recent(&store, "events", 10).await.unwrap();
+        assert_eq!(types(&events), vec!["open", "closed"]);
+        assert_eq!(events[1].name, "state");
+        assert_eq!(events[1].timestamp, 4);
+
+        // The last state is kept in the store, e.g. across restarts
+        let state = store.get_values(&["events:state"]).await.unwrap();
+        assert_eq!(state, vec![Some("open".into())]);
+        let log = EventLog::new();
+        log.observe_state(&store, Some(false), at(6)).await.unwrap();
+        let events = recent(&store, "events", 10).await.unwrap();
+        assert_eq!(types(&events), vec!["closed", "open", "closed"]);
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_state_changes() {
+        let (log, store) = (EventLog::new().max_len(0), Arc::new(MemoryStore::new()));
+        log.observe_state(&*store, Some(false), at(0)).await.unwrap();
+        let updates = (1..=100).map(|secs| {
+            let (log, store) = (log.clone(), store.clone());
+            tokio::spawn(async move {
+                let open = Some(secs % 2 == 1);
+                log.observe_state(&*store, open, at(secs)).await.unwrap();
+            })
+        });
+        for update in updates.collect::<Vec<_>>() {
+            update.await.unwrap();
+        }
+
+        // Starting closed, the space opened once more than it closed if it
+        // ended up open. Every change was recorded exactly once.
+        let events = recent(&*store, "events", 1000).await.unwrap();
+        let opened = events.iter().filter(|event| event.type_ == "open").count();
+        let closed = events.len() - opened;
+        let state = store.get_values(&["events:state"]).await.unwrap();
+        match state[0].as_deref() {
+            Some("open") => assert_eq!(opened, closed + 1),
+            Some("closed") => assert_eq!(opened, closed),
+            state => panic!("Unexpected state {:?}", state),
+        }
+    }
+
+    #[test]
+    fn sensors() {
+        let log = EventLog::new().record_sensor("door_locked");
+        assert!(log.records_sensor("door_locked"));
+        assert!(!log.records_sensor("temperature"));
+        assert!(!EventLog::new().records_sensor("door_locked"));
+    }
+
+    #[test]
+    fn tokens() {
+        assert!(!EventLog::new().allows(None));
+        assert!(!EventLog::new().allows(Some("")));
+        let log = EventLog::new().allow_token("secret");
+        assert!(log.allows(Some("secret")));
+        assert!(!log.allows(Some("guess")));
+        assert!(!log.allows(None));
+    }
+}
@@ -30,6 +30,7 @@
 //! ```toml
 //! [dependencies]
 //! spaceapi-server = "0.7"
+//! tokio = { version = "1", features = ["macros", "rt-multi-thread"] }
 //! ```
 //!
 //! Create a `main.rs`:
@@ -38,7 +39,8 @@
 //! use spaceapi_server::api::{Contact, Location, StatusBuilder};
 //! use spaceapi_server::SpaceapiServerBuilder;
 //!
-//! fn main() {
+//! #[tokio::main]
+//! async fn main() {
 //!     // Create new minimal v14 Status instance
 //!     let status = StatusBuilder::v14("coredump")
 //!         .logo("https://www.coredump.ch/logo.png")
@@ -64,14 +66,27 @@
 //!         .unwrap();
 //!
 //!     // Serve!
-//!     let _ = server.serve("127.0.0.1:8000");
+//!     let _ = server.serve("127.0.0.1:8000").await;
 //! }
 //! ```
 //!
 //! Now you can build and run your binary with `cargo run`. Running this code
-//! starts a HTTP server instance on port 8000. You can also override the port
+//! starts an async HTTP server instance on port 8000. You can also override the port
 //! by setting the `PORT` environment variable.
 //!
+//! The following environment variables are supported, which is useful for
+//! container deployments:
+//!
+//! - `HOST`: Override the host to listen on (e.g. `0.0.0.0`)
+//! - `PORT`: Override the port to listen on
+//! - `REDIS_URL`: Override the Redis connection string (e.g. `redis://redis/`)
+//!
+//! Instead of building the status in code, you can also load it from a
+//! SpaceAPI JSON file with
+//! [`SpaceapiServerBuilder::from_status_file`](struct.SpaceapiServerBuilder.html#method.from_status_file).
+//! The file is watched for changes, so you can update your contact details or
+//! projects without restarting the server.
+//!
 //! See the
 //! [`examples/`](https://github.com/spaceapi-community/spaceapi-server-rs/tree/master/examples)
 //! directory for some other examples.
@@ -188,24 +203,148 @@
 //! ```
 //!
 //! The keys need to match the IDs you used when registering the sensor.
+//!
+//! ### Custom Stores
+//!
+//! Redis is accessed through the async [`store::Store`](store/trait.Store.html)
+//! trait. To keep the dynamic data somewhere else, implement it and pass your
+//! store to
+//! [`SpaceapiServerBuilder::store`](struct.SpaceapiServerBuilder.html#method.store).
+//!
+//!
+//! ## SpaceAPI Versions
+//!
+//! The status is served at `/` in the version it was built with. In
+//! addition, it is served in the v0.13 schema at `/v13` and in the v14
+//! schema at `/v14`, for clients that expect a specific version. Fields
+//! that were renamed between the versions are mapped (e.g. `contact.jabber`
+//! and `contact.xmpp`), fields that don't exist in the requested version are
+//! omitted, and the fields required by v0.13 are filled in. Sensor values
+//! and status modifiers apply to all versions alike. v0.13 requires an
+//! issue report channel, so without an issue mail, email, mailing list or
+//! Twitter contact, `/v13` responds with `404 Not Found`. v14 only allows a
+//! known state, so if neither a sensor nor the static status sets
+//! `state.open`, `/v14` omits the state.
+//!
+//! The v15 schema is not supported yet, as the underlying
+//! [spaceapi](https://docs.rs/spaceapi/) crate doesn't support it either.
+//!
+//! ## Schema Validation
+//!
+//! With the `validation` feature enabled, the served status can be checked
+//! against bundled copies of the SpaceAPI JSON schemas, after all sensors and
+//! status modifiers have been applied. Use
+//! `SpaceapiServerBuilder::validation` with `ValidationMode::Log` to log
+//! violations, or with `ValidationMode::Strict` to respond with an error
+//! instead of serving an invalid status. `SpaceapiServerBuilder::validate`
+//! checks the static status once, e.g. at startup.
+//!
+//! ## Views
+//!
+//! To show members on the space network more than the public internet, add
+//! [views](views/index.html) with `SpaceapiServerBuilder::add_view`. A view
+//! is selected by the client address or a bearer token and hides the
+//! specified fields, e.g. `sensors.people_now_present.names`. The last view
+//! applies to all other clients. The status is validated as it is served
+//! through the view.
+//!
+//! ## Presence
+//!
+//! With `SpaceapiServerBuilder::presence`, members check in and out with
+//! `PUT` and `DELETE` requests to `/presence/<name>/`, authenticated by
+//! their own token. The names of the
+//! members who opt in with `visible=true` are served in the
+//! `people_now_present` sensor, see the [`presence`](presence/index.html)
+//! module.
+//!
+//! ## Events
+//!
+//! With `SpaceapiServerBuilder::event_log`, sensor updates, state changes
+//! and check-ins are recorded in an event log, and other events can be
+//! posted to `/events`. The
+//! [`RecentEvents`](modifiers/struct.RecentEvents.html) modifier serves the
+//! most recent ones in the `events` field, see the
+//! [`events`](events/index.html) module.
+//!
+//! ## Monitoring
+//!
+//! For use with container orchestrators and uptime monitors, the server
+//! provides two additional endpoints:
+//!
+//! - `GET /healthz` always returns `200 OK` as long as the process is serving
+//!   requests.
+//! - `GET /readyz` pings the store (e.g. Redis) and returns `200 OK` if it
+//!   answers within two seconds, or `503 Service Unavailable` with details in
+//!   the JSON body if it doesn't.
+//!
+//! Metrics in the Prometheus text format are available at `GET /metrics`.
+//! They include request counts per route and status code, sensor read
+//! failures and updates per data key, Redis latency histograms and the
+//! current values of all numeric sensors.
+//!
+//! ## Embedding
+//!
+//! If you already run an [axum](https://docs.rs/axum/) application, you can
+//! mount the SpaceAPI routes into it instead of calling `serve`, using
+//! `SpaceapiServer::into_router` or `SpaceapiServer::into_router_with_prefix`:
+//!
+//! ```no_run
+//! # use spaceapi_server::SpaceapiServer;
+//! # fn portal(server: SpaceapiServer) -> axum::Router {
+//! axum::Router::new()
+//!     .route("/", axum::routing::get(|| async { "Member portal" }))
+//!     .merge(server.into_router_with_prefix("/spaceapi"))
+//! # }
+//! ```
+//!
+//! ## Unix Sockets and Socket Activation
+//!
+//! On Unix, `SpaceapiServer::serve_unix` listens on a Unix domain socket
+//! instead of a TCP port, which is handy behind a reverse proxy like nginx.
+//! `SpaceapiServer::serve_systemd` uses the socket passed by systemd socket
+//! activation (`LISTEN_FDS`).
+//!
+//! ## TLS
+//!
+//! With the `tls` feature enabled, `SpaceapiServer::serve_tls` serves HTTPS
+//! directly, using a PEM encoded certificate chain and private key. The
+//! files are checked for changes every minute, so renewed certificates
+//! (e.g. from Let's Encrypt) are picked up without a restart.
+//!
+//! ## Shutdown
+//!
+//! To stop a running server, get a `ShutdownHandle` with
+//! `SpaceapiServer::shutdown_handle` before calling `serve`. Awaiting
+//! `shutdown(timeout)` on it closes the listening socket and waits for
+//! in-flight requests to finish, after which the `serve` future completes.
 
 #![deny(missing_docs)]
 #![doc(html_root_url = "https://docs.rs/spaceapi-server")]
 
 pub use spaceapi as api;
 
-pub use iron::error::HttpResult;
-pub use iron::Listening;
-
+mod auth;
+#[cfg(feature = "config")]
+pub mod config;
 mod errors;
+pub mod events;
 pub mod modifiers;
+pub mod presence;
 mod sensors;
 mod server;
+pub mod store;
 mod types;
+#[cfg(feature = "validation")]
+mod validation;
+pub mod views;
 
 pub use crate::errors::SpaceapiServerError;
+pub use crate::server::ReloadHandle;
+pub use crate::server::ShutdownHandle;
 pub use crate::server::SpaceapiServer;
 pub use crate::server::SpaceapiServerBuilder;
+#[cfg(feature = "validation")]
+pub use crate::validation::ValidationMode;
 
 /// Return own crate version. Used in API responses.
 pub fn get_version() -> &'static str {
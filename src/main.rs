@@ -0,0 +1,104 @@
+//! A SpaceAPI server driven by a config file.
+//!
+//! Usage: `spaceapi-server [CONFIG_FILE]`
+//!
+//! The config file defaults to the value of the `SPACEAPI_CONFIG` environment
+//! variable, or `spaceapi.toml` in the current directory. See the
+//! documentation of the `spaceapi_server::config` module for the format.
+//!
+//! On Unix, sending `SIGHUP` to the process reloads the static status, the
+//! sensors, the status modifiers, the views, the presence list and the event
+//! log from the config file. Changes to the other settings require a
+//! restart. `SIGTERM` and `SIGINT` shut the server down gracefully, waiting
+//! up to 10 seconds for in-flight requests.
+//!
+//! When started through systemd socket activation (`LISTEN_FDS` is set), the
+//! server uses the passed socket and ignores the `listen` setting.
+
+use std::env;
+use std::ffi::OsString;
+use std::process;
+use std::time::Duration;
+
+use spaceapi_server::config::Config;
+
+/// Reload the config file whenever the process receives a `SIGHUP`.
+#[cfg(unix)]
+fn reload_on_sighup(path: OsString, reload_handle: spaceapi_server::ReloadHandle) {
+    use log::{error, info};
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut signals = signal(SignalKind::hangup()).expect("Could not register SIGHUP handler");
+    tokio::spawn(async move {
+        while signals.recv().await.is_some() {
+            let reloaded = Config::from_file(&path)
+                .and_then(|config| config.server_builder())
+                .and_then(|builder| reload_handle.reload(builder));
+            match reloaded {
+                Ok(()) => info!("Reloaded config file {:?}", path),
+                Err(e) => error!("Could not reload config, keeping the previous one: {}", e),
+            }
+        }
+    });
+}
+
+/// Wait until the process receives a `SIGTERM` or `SIGINT`, then shut down
+/// the server.
+async fn shutdown_on_signal(shutdown_handle: spaceapi_server::ShutdownHandle) {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        let mut terminate = signal(SignalKind::terminate()).expect("Could not register SIGTERM handler");
+        tokio::select! {
+            _ = terminate.recv() => {}
+            _ = tokio::signal::ctrl_c() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = tokio::signal::ctrl_c().await;
+
+    log::info!("Shutting down");
+    shutdown_handle.shutdown(Duration::from_secs(10)).await;
+}
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+
+    let path = env::args_os()
+        .nth(1)
+        .or_else(|| env::var_os("SPACEAPI_CONFIG"))
+        .unwrap_or_else(|| "spaceapi.toml".into());
+    let server = Config::from_file(&path).and_then(|config| {
+        let server = config.server_builder()?.build()?;
+        Ok((config.listen, server))
+    });
+    let (listen, server) = match server {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("Could not initialize server: {}", e);
+            process::exit(1);
+        }
+    };
+
+    #[cfg(unix)]
+    reload_on_sighup(path, server.reload_handle());
+    tokio::spawn(shutdown_on_signal(server.shutdown_handle()));
+
+    // Serve!
+    #[cfg(unix)]
+    let result = if env::var_os("LISTEN_FDS").is_some() {
+        server.serve_systemd().await
+    } else if let Some(path) = listen.strip_prefix("unix:") {
+        server.serve_unix(path).await
+    } else {
+        server.serve(&*listen).await
+    };
+    #[cfg(not(unix))]
+    let result = server.serve(&*listen).await;
+    if let Err(e) = result {
+        eprintln!("Could not start the server: {}", e);
+        process::exit(1);
+    }
+}
@@ -3,43 +3,379 @@
 
 use crate::api::{self, sensors};
 
+mod contextual;
+mod message;
+#[cfg(feature = "opening-hours")]
+mod opening_hours;
+mod recent_events;
+
+pub use self::contextual::{Context, ContextualStatusModifier, ErrorPolicy, ModifierError};
+pub use self::message::Message;
+pub use self::recent_events::RecentEvents;
+
+#[cfg(feature = "opening-hours")]
+pub use self::opening_hours::{OpeningHours, Precedence};
+#[cfg(feature = "opening-hours")]
+pub use chrono::{NaiveDate, NaiveTime, Weekday};
+#[cfg(feature = "opening-hours")]
+pub use chrono_tz::Tz;
+
 /// `StatusModifier`s are used to modify the status
 pub trait StatusModifier: Send + Sync {
     /// Called after all registered sensors are read
     fn modify(&self, status: &mut api::Status);
+
+    /// Called instead of ``modify`` with the locale preferred by the client,
+    /// if it is one of the locales returned by ``locales``. Messages should
+    /// be set in that locale.
+    ///
+    /// The default implementation calls ``modify``.
+    fn modify_localized(&self, status: &mut api::Status, locale: Option<&str>) {
+        let _ = locale;
+        self.modify(status);
+    }
+
+    /// Return the locales the messages of this modifier are available in,
+    /// e.g. `de` or `de-CH`.
+    fn locales(&self) -> Vec<String> {
+        vec![]
+    }
+
+    /// Return whether the modified status only depends on the status passed
+    /// in and the locale. The rendered status is then shared with other
+    /// clients through the response cache, see
+    /// [`ContextualStatusModifier::cacheable`].
+    ///
+    /// Defaults to `false`, as the modifier may read a clock or other
+    /// external data.
+    fn cacheable(&self) -> bool {
+        false
+    }
+}
+
+/// Closures are status modifiers, too.
+///
+/// ```
+/// use spaceapi_server::api;
+/// use spaceapi_server::modifiers::StatusModifier;
+///
+/// let modifier = |status: &mut api::Status| status.space.push_str(" (beta)");
+/// let mut status = api::Status::default();
+/// modifier.modify(&mut status);
+/// assert_eq!(status.space, " (beta)");
+/// ```
+impl<F> StatusModifier for F
+where
+    F: Fn(&mut api::Status) + Send + Sync,
+{
+    fn modify(&self, status: &mut api::Status) {
+        self(status);
+    }
+}
+
+/// A status modifier that only applies if a predicate holds, see [`when`].
+#[derive(Debug, Clone)]
+pub struct When<P, M> {
+    predicate: P,
+    modifier: M,
+}
+
+/// Apply the modifier only if the predicate returns `true` for the status,
+/// as modified by the sensors and the preceding status modifiers. The
+/// predicate should only depend on the status, the result is
+/// [cacheable](StatusModifier::cacheable) if the modifier is.
+///
+/// ```
+/// use spaceapi_server::api;
+/// use spaceapi_server::modifiers::{self, StateFromPeopleNowPresent};
+///
+/// // Keep the state set by an operator instead of overriding it
+/// let modifier = modifiers::when(
+///     |status: &api::Status| status.state.as_ref().and_then(|state| state.open).is_none(),
+///     StateFromPeopleNowPresent,
+/// );
+/// ```
+pub fn when<P, M>(predicate: P, modifier: M) -> When<P, M>
+where
+    P: Fn(&api::Status) -> bool + Send + Sync,
+    M: StatusModifier,
+{
+    When { predicate, modifier }
+}
+
+impl<P, M> StatusModifier for When<P, M>
+where
+    P: Fn(&api::Status) -> bool + Send + Sync,
+    M: StatusModifier,
+{
+    fn modify(&self, status: &mut api::Status) {
+        if (self.predicate)(status) {
+            self.modifier.modify(status);
+        }
+    }
+
+    fn modify_localized(&self, status: &mut api::Status, locale: Option<&str>) {
+        if (self.predicate)(status) {
+            self.modifier.modify_localized(status, locale);
+        }
+    }
+
+    fn locales(&self) -> Vec<String> {
+        self.modifier.locales()
+    }
+
+    fn cacheable(&self) -> bool {
+        self.modifier.cacheable()
+    }
 }
 
 /// This modifier updates the opening state based on the
 /// first people now present sensor (if present).
+///
+/// The message is "{count} people here right now" or "1 person here right
+/// now", and left unchanged while nobody is present. Use
+/// [`with_message`](#method.with_message) for another message.
+#[derive(Debug, Clone, Copy, Default)]
 pub struct StateFromPeopleNowPresent;
 
+impl StateFromPeopleNowPresent {
+    /// Use the specified message template instead of the default message,
+    /// see [`StateFromPeopleNowPresentWithMessage`].
+    pub fn with_message<M: Into<Message>>(message: M) -> StateFromPeopleNowPresentWithMessage {
+        StateFromPeopleNowPresentWithMessage {
+            message: message.into(),
+        }
+    }
+}
+
 impl StatusModifier for StateFromPeopleNowPresent {
     fn modify(&self, status: &mut api::Status) {
-        // Update state depending on number of people present
-        let people_now_present: Option<u64> = status
-            .sensors
-            .as_ref()
-            .and_then(|sensors: &sensors::Sensors| sensors.people_now_present.first())
-            .map(|sensor: &sensors::PeopleNowPresentSensor| sensor.value);
-        if let Some(count) = people_now_present {
-            let mut state = status.state.clone().unwrap_or_default();
-            state.open = Some(count > 0);
-            // comparison chain is actually cleaner here IMO
-            #[allow(clippy::comparison_chain)]
-            if count == 1 {
-                state.message = Some(format!("{} person here right now", count));
-            } else if count > 1 {
-                state.message = Some(format!("{} people here right now", count));
+        let message = Message::new("{count} people here right now").one("{count} person here right now");
+        set_state_from_people_now_present(status, &message, None);
+    }
+
+    fn cacheable(&self) -> bool {
+        true
+    }
+}
+
+/// This modifier updates the opening state based on the first people now
+/// present sensor (if present), like [`StateFromPeopleNowPresent`], with a
+/// configurable message.
+///
+/// The message is set from a [`Message`] with the `{count}` placeholder.
+/// Without a `zero` form, the message is left unchanged while nobody is
+/// present.
+///
+/// ```
+/// use spaceapi_server::modifiers::{Message, StateFromPeopleNowPresent};
+///
+/// let modifier = StateFromPeopleNowPresent::with_message(
+///     Message::new("{count} people here right now")
+///         .one("{count} person here right now")
+///         .translation("de", Message::new("{count} Personen anwesend").one("Eine Person anwesend")),
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct StateFromPeopleNowPresentWithMessage {
+    message: Message,
+}
+
+impl StatusModifier for StateFromPeopleNowPresentWithMessage {
+    fn modify(&self, status: &mut api::Status) {
+        self.modify_localized(status, None);
+    }
+
+    fn modify_localized(&self, status: &mut api::Status, locale: Option<&str>) {
+        set_state_from_people_now_present(status, &self.message, locale);
+    }
+
+    fn locales(&self) -> Vec<String> {
+        self.message.locales().map(Into::into).collect()
+    }
+
+    fn cacheable(&self) -> bool {
+        true
+    }
+}
+
+fn set_state_from_people_now_present(status: &mut api::Status, message: &Message, locale: Option<&str>) {
+    // Update state depending on number of people present
+    let people_now_present: Option<u64> = status
+        .sensors
+        .as_ref()
+        .and_then(|sensors: &sensors::Sensors| sensors.people_now_present.first())
+        .map(|sensor: &sensors::PeopleNowPresentSensor| sensor.value);
+    if let Some(count) = people_now_present {
+        let mut state = status.state.clone().unwrap_or_default();
+        state.open = Some(count > 0);
+        if count > 0 || message.has_zero(locale) {
+            state.message = Some(message.render_count(locale, count, &[]));
+        }
+        status.state = Some(state);
+    }
+}
+
+/// Which `door_locked` sensors decide the opening state in
+/// [`StateFromDoorLocked`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum Doors {
+    /// The first `door_locked` sensor.
+    #[default]
+    First,
+    /// The first `door_locked` sensor with the specified name.
+    Named(String),
+    /// The space is open if any door is unlocked.
+    Any,
+    /// The space is open only if all doors are unlocked.
+    All,
+}
+
+/// This modifier updates the opening state based on the `door_locked`
+/// sensors: the space is open while the door is unlocked.
+///
+/// By default, the first `door_locked` sensor is used, see [`Doors`] for
+/// the alternatives. If there is no matching sensor, the state is left
+/// unchanged.
+///
+/// ```
+/// use spaceapi_server::modifiers::{Doors, StateFromDoorLocked};
+///
+/// let modifier = StateFromDoorLocked::default()
+///     .doors(Doors::Any)
+///     .open_message("Come in!")
+///     .closed_message("All doors are locked");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StateFromDoorLocked {
+    doors: Doors,
+    open_message: Option<Message>,
+    closed_message: Option<Message>,
+}
+
+impl StateFromDoorLocked {
+    /// Set which sensors decide the opening state.
+    pub fn doors(mut self, doors: Doors) -> Self {
+        self.doors = doors;
+        self
+    }
+
+    /// Set the message while the space is open. By default, the message
+    /// is left unchanged.
+    pub fn open_message<M: Into<Message>>(mut self, message: M) -> Self {
+        self.open_message = Some(message.into());
+        self
+    }
+
+    /// Set the message while the space is closed. By default, the message
+    /// is left unchanged.
+    pub fn closed_message<M: Into<Message>>(mut self, message: M) -> Self {
+        self.closed_message = Some(message.into());
+        self
+    }
+
+    /// Return whether the space is open according to the sensors, if any
+    /// sensor matches.
+    fn is_open(&self, doors: &[sensors::DoorLockedSensor]) -> Option<bool> {
+        let unlocked = |sensor: &sensors::DoorLockedSensor| !sensor.value;
+        match &self.doors {
+            Doors::First => doors.first().map(unlocked),
+            Doors::Named(name) => doors
+                .iter()
+                .find(|sensor| sensor.metadata.name.as_ref() == Some(name))
+                .map(unlocked),
+            _ if doors.is_empty() => None,
+            Doors::Any => Some(doors.iter().any(unlocked)),
+            Doors::All => Some(doors.iter().all(unlocked)),
+        }
+    }
+}
+
+impl StatusModifier for StateFromDoorLocked {
+    fn modify(&self, status: &mut api::Status) {
+        self.modify_localized(status, None);
+    }
+
+    fn modify_localized(&self, status: &mut api::Status, locale: Option<&str>) {
+        let open = match &status.sensors {
+            Some(sensors) => self.is_open(&sensors.door_locked),
+            None => None,
+        };
+        if let Some(open) = open {
+            let state = status.state.get_or_insert_with(Default::default);
+            state.open = Some(open);
+            let message = if open {
+                &self.open_message
+            } else {
+                &self.closed_message
+            };
+            if let Some(message) = message {
+                state.message = Some(message.render(locale, &[]));
             }
-            status.state = Some(state);
         }
     }
+
+    fn locales(&self) -> Vec<String> {
+        let messages = self.open_message.iter().chain(&self.closed_message);
+        let mut locales: Vec<String> = messages.flat_map(Message::locales).map(Into::into).collect();
+        locales.sort();
+        locales.dedup();
+        locales
+    }
+
+    fn cacheable(&self) -> bool {
+        true
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    mod closures {
+        use super::*;
+
+        fn is_open(status: &api::Status) -> bool {
+            status.state.as_ref().and_then(|state| state.open) == Some(true)
+        }
+
+        fn set_message(status: &mut api::Status) {
+            status.state.get_or_insert_with(Default::default).message = Some("Come in".into());
+        }
+
+        #[test]
+        fn closure() {
+            let mut status = api::Status::default();
+            set_message.modify(&mut status);
+            assert_eq!(status.state.unwrap().message.as_deref(), Some("Come in"));
+        }
+
+        #[test]
+        fn when_predicate_holds() {
+            let modifier = when(is_open, set_message);
+            let mut status = api::Status::default();
+            modifier.modify(&mut status);
+            assert_eq!(status.state, None);
+
+            status.state = Some(api::State {
+                open: Some(true),
+                ..Default::default()
+            });
+            modifier.modify(&mut status);
+            assert_eq!(status.state.unwrap().message.as_deref(), Some("Come in"));
+        }
+
+        #[test]
+        fn when_forwards_locales() {
+            let modifier = when(
+                |_: &api::Status| true,
+                StateFromDoorLocked::default()
+                    .open_message(Message::new("Open").translation("de", "Offen".into())),
+            );
+            assert_eq!(modifier.locales(), vec!["de"]);
+        }
+    }
+
     mod state_from_people_now_present {
         use super::*;
 
@@ -129,5 +465,120 @@ mod tests {
                 Some("2 people here right now".to_string())
             );
         }
+
+        #[test]
+        fn custom_message() {
+            let modifier = StateFromPeopleNowPresent::with_message(
+                Message::new("{count} hackers")
+                    .zero("Nobody here")
+                    .translation("de", Message::new("{count} Hacker")),
+            );
+            assert_eq!(modifier.locales(), vec!["de"]);
+            let mut status = api::Status {
+                sensors: Some(sensors::Sensors {
+                    people_now_present: vec![make_pnp_sensor(0)],
+                    ..sensors::Sensors::default()
+                }),
+                ..api::Status::default()
+            };
+            modifier.modify(&mut status);
+            let state = status.state.as_ref().unwrap();
+            assert_eq!(state.open, Some(false));
+            assert_eq!(state.message.as_deref(), Some("Nobody here"));
+            status.sensors.as_mut().unwrap().people_now_present[0].value = 3;
+            modifier.modify_localized(&mut status, Some("de"));
+            assert_eq!(status.state.unwrap().message.as_deref(), Some("3 Hacker"));
+        }
+    }
+
+    mod state_from_door_locked {
+        use super::*;
+
+        fn status(doors: &[(&str, bool)]) -> api::Status {
+            let door_locked = doors
+                .iter()
+                .map(|&(name, locked)| sensors::DoorLockedSensor {
+                    metadata: sensors::SensorMetadataWithLocation {
+                        name: Some(name.into()),
+                        location: "Entrance".into(),
+                        description: None,
+                    },
+                    value: locked,
+                })
+                .collect();
+            api::Status {
+                sensors: Some(sensors::Sensors {
+                    door_locked,
+                    ..sensors::Sensors::default()
+                }),
+                ..api::Status::default()
+            }
+        }
+
+        fn open(modifier: StateFromDoorLocked, doors: &[(&str, bool)]) -> Option<bool> {
+            let mut status = status(doors);
+            modifier.modify(&mut status);
+            status.state.and_then(|state| state.open)
+        }
+
+        #[test]
+        fn no_sensors() {
+            assert_eq!(open(StateFromDoorLocked::default(), &[]), None);
+            let mut status = api::Status::default();
+            StateFromDoorLocked::default().modify(&mut status);
+            assert_eq!(status.state, None);
+        }
+
+        #[test]
+        fn first_door() {
+            let doors = [("front", false), ("back", true)];
+            assert_eq!(open(StateFromDoorLocked::default(), &doors), Some(true));
+            let doors = [("front", true), ("back", false)];
+            assert_eq!(open(StateFromDoorLocked::default(), &doors), Some(false));
+        }
+
+        #[test]
+        fn named_door() {
+            let doors = [("front", true), ("back", false)];
+            let modifier = || StateFromDoorLocked::default().doors(Doors::Named("back".into()));
+            assert_eq!(open(modifier(), &doors), Some(true));
+            assert_eq!(open(modifier(), &doors[..1]), None);
+        }
+
+        #[test]
+        fn any_or_all_doors() {
+            let any = || StateFromDoorLocked::default().doors(Doors::Any);
+            let all = || StateFromDoorLocked::default().doors(Doors::All);
+            let doors = [("front", true), ("back", false)];
+            assert_eq!(open(any(), &doors), Some(true));
+            assert_eq!(open(all(), &doors), Some(false));
+            let doors = [("front", false), ("back", false)];
+            assert_eq!(open(all(), &doors), Some(true));
+            let doors = [("front", true), ("back", true)];
+            assert_eq!(open(any(), &doors), Some(false));
+            assert_eq!(open(all(), &[]), None);
+        }
+
+        #[test]
+        fn messages() {
+            let modifier = StateFromDoorLocked::default()
+                .open_message("Come in")
+                .closed_message("Closed");
+            let mut door_status = status(&[("front", false)]);
+            modifier.modify(&mut door_status);
+            assert_eq!(door_status.state.unwrap().message.as_deref(), Some("Come in"));
+            let mut door_status = status(&[("front", true)]);
+            modifier.modify(&mut door_status);
+            assert_eq!(door_status.state.unwrap().message.as_deref(), Some("Closed"));
+
+            // Messages are left unchanged by default
+            let mut door_status = status(&[("front", true)]);
+            door_status.state = Some(api::State {
+                message: Some("Hello".into()),
+                ..Default::default()
+            });
+            StateFromDoorLocked::default().modify(&mut door_status);
+            assert_eq!(door_status.state.unwrap().message.as_deref(), Some("Hello"));
+        }
     }
 }
@@ -0,0 +1,341 @@
+//! Status modifiers with access to the store and the request.
+
+use std::net::SocketAddr;
+use std::time::SystemTime;
+
+use std::borrow::Cow;
+
+use async_trait::async_trait;
+use axum::http::HeaderMap;
+use quick_error::quick_error;
+
+use super::StatusModifier;
+use crate::api;
+use crate::store::{Store, StoreError};
+
+quick_error! {
+    /// A ``ModifierError`` is returned by a status modifier that could not
+    /// modify the status.
+    #[derive(Debug)]
+    pub enum ModifierError {
+        /// A problem with the store occurred.
+        Store(err: StoreError) {
+            from()
+            display("{}", err)
+            source(err)
+        }
+        /// Another error happened.
+        Message(err: Cow<'static, str>) {
+            display("{}", err)
+            from(s: &'static str) -> (s.into())
+            from(s: String) -> (s.into())
+        }
+    }
+}
+
+/// What the server does when a status modifier returns an error.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Log the error and continue with the next modifier.
+    #[default]
+    Skip,
+    /// Log the error, continue with the next modifier and list the error in
+    /// the `ext_modifier_errors` field of the served status.
+    Degrade,
+    /// Log the error and respond with `503 Service Unavailable`.
+    Fail,
+}
+
+/// The context a [`ContextualStatusModifier`] is called with.
+pub struct Context<'a> {
+    store: &'a dyn Store,
+    remote_addr: Option<SocketAddr>,
+    headers: &'a HeaderMap,
+    path: &'a str,
+    locale: Option<&'a str>,
+    view: Option<&'a str>,
+    static_open: Option<bool>,
+    now: SystemTime,
+}
+
+impl<'a> Context<'a> {
+    /// Create a context for a request to the specified path, e.g. to test a
+    /// modifier. The other request metadata is empty and the current time
+    /// is the time of the call.
+    pub fn new(store: &'a dyn Store, headers: &'a HeaderMap, path: &'a str) -> Context<'a> {
+        Context {
+            store,
+            remote_addr: None,
+            headers,
+            path,
+            locale: None,
+            view: None,
+            static_open: None,
+            now: SystemTime::now(),
+        }
+    }
+
+    /// Set the address of the client.
+    pub fn with_remote_addr(mut self, remote_addr: Option<SocketAddr>) -> Self {
+        self.remote_addr = remote_addr;
+        self
+    }
+
+    /// Set the negotiated locale.
+    pub fn with_locale(mut self, locale: Option<&'a str>) -> Self {
+        self.locale = locale;
+        self
+    }
+
+    /// Set the name of the view selected for the client.
+    pub fn with_view(mut self, view: Option<&'a str>) -> Self {
+        self.view = view;
+        self
+    }
+
+    /// Set the `state.open` of the static status.
+    pub fn with_static_open(mut self, static_open: Option<bool>) -> Self {
+        self.static_open = static_open;
+        self
+    }
+
+    /// Set the current time.
+    pub fn with_now(mut self, now: SystemTime) -> Self {
+        self.now = now;
+        self
+    }
+
+    /// The store holding the sensor values, e.g. to read additional keys.
+    pub fn store(&self) -> &'a dyn Store {
+        self.store
+    }
+
+    /// The address of the client, if known. Clients connected through a
+    /// Unix socket have no address.
+    pub fn remote_addr(&self) -> Option<SocketAddr> {
+        self.remote_addr
+    }
+
+    /// The headers of the request.
+    pub fn headers(&self) -> &'a HeaderMap {
+        self.headers
+    }
+
+    /// The path of the request, e.g. `/` or `/v14`.
+    pub fn path(&self) -> &'a str {
+        self.path
+    }
+
+    /// The locale the messages should be set in, if the client prefers one
+    /// of the locales returned by
+    /// [`ContextualStatusModifier::available_locales`].
+    pub fn locale(&self) -> Option<&'a str> {
+        self.locale
+    }
+
+    /// The name of the [view](../views/index.html) selected for the client,
+    /// if any.
+    pub fn view(&self) -> Option<&'a str> {
+        self.view
+    }
+
+    /// The `state.open` of the static status, before any modifier was
+    /// applied. A modifier can compare it with the current state to tell
+    /// whether a preceding modifier set the state.
+    pub fn static_open(&self) -> Option<bool> {
+        self.static_open
+    }
+
+    /// The time the status is rendered at. All modifiers of a request see
+    /// the same time.
+    pub fn now(&self) -> SystemTime {
+        self.now
+    }
+}
+
+/// A status modifier with access to the store and the request, e.g. to read
+/// a "closed for an event" flag from Redis or to show more details to
+/// clients on the internal network.
+///
+/// Every [`StatusModifier`] is a `ContextualStatusModifier`, so both kinds
+/// can be registered with the server and are applied in the order they were
+/// added. A `StatusModifier` is cacheable if its
+/// [`cacheable`](StatusModifier::cacheable) returns `true`.
+///
+/// A modifier that can't modify the status returns an error. What the
+/// server does then depends on the [`ErrorPolicy`] the modifier was
+/// registered with.
+///
+/// With a response cache, the status is only served from the cache if all
+/// modifiers are [cacheable](ContextualStatusModifier::cacheable).
+///
+/// ```
+/// use async_trait::async_trait;
+/// use spaceapi_server::api;
+/// use spaceapi_server::modifiers::{Context, ContextualStatusModifier, ModifierError};
+///
+/// /// Closes the space while the `closed_for_event` key is set.
+/// struct ClosedForEvent;
+///
+/// #[async_trait]
+/// impl ContextualStatusModifier for ClosedForEvent {
+///     async fn modify_with_context(
+///         &self,
+///         status: &mut api::Status,
+///         context: &Context<'_>,
+///     ) -> Result<(), ModifierError> {
+///         let values = context.store().get_values(&["closed_for_event"]).await?;
+///         if let Some(event) = &values[0] {
+///             let state = status.state.get_or_insert_with(Default::default);
+///             state.open = Some(false);
+///             state.message = Some(format!("Closed for {}", event));
+///         }
+///         Ok(())
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait ContextualStatusModifier: Send + Sync {
+    /// Called after all registered sensors are read
+    async fn modify_with_context(
+        &self,
+        status: &mut api::Status,
+        context: &Context<'_>,
+    ) -> Result<(), ModifierError>;
+
+    /// Return the locales the messages of this modifier are available in,
+    /// e.g. `de` or `de-CH`.
+    fn available_locales(&self) -> Vec<String> {
+        vec![]
+    }
+
+    /// Return whether the modified status only depends on the store, the
+    /// path, the locale, the view and the current time. The rendered status
+    /// is then shared with other clients through the response cache, until
+    /// it expires or the store is updated through the HTTP API.
+    ///
+    /// Defaults to `false`, i.e. every request is rendered, as the modifier
+    /// may depend on other properties of the client.
+    fn cacheable(&self) -> bool {
+        false
+    }
+}
+
+#[async_trait]
+impl<M: StatusModifier> ContextualStatusModifier for M {
+    async fn modify_with_context(
+        &self,
+        status: &mut api::Status,
+        context: &Context<'_>,
+    ) -> Result<(), ModifierError> {
+        self.modify_localized(status, context.locale());
+        Ok(())
+    }
+
+    fn available_locales(&self) -> Vec<String> {
+        self.locales()
+    }
+
+    fn cacheable(&self) -> bool {
+        StatusModifier::cacheable(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+    use crate::store::MemoryStore;
+
+    struct ClosedForEvent;
+
+    #[async_trait]
+    impl ContextualStatusModifier for ClosedForEvent {
+        async fn modify_with_context(
+            &self,
+            status: &mut api::Status,
+            context: &Context<'_>,
+        ) -> Result<(), ModifierError> {
+            let values = context.store().get_values(&["event"]).await?;
+            let internal = context.remote_addr().is_some_and(|addr| addr.ip().is_loopback());
+            let state = status.state.get_or_insert_with(Default::default);
+            state.message = values[0].clone().filter(|_| internal);
+            state.lastchange = context
+                .now()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .ok()
+                .map(|since| since.as_secs());
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn context() {
+        let store = MemoryStore::new();
+        store.set_value("event", "Party").await.unwrap();
+        let headers = HeaderMap::new();
+        let now = SystemTime::UNIX_EPOCH + Duration::from_secs(42);
+        let context = Context::new(&store, &headers, "/").with_now(now);
+
+        let mut status = api::Status::default();
+        ClosedForEvent
+            .modify_with_context(&mut status, &context)
+            .await
+            .unwrap();
+        let state = status.state.unwrap();
+        assert_eq!(state.message, None);
+        assert_eq!(state.lastchange, Some(42));
+
+        let context = context.with_remote_addr(Some("127.0.0.1:1234".parse().unwrap()));
+        let mut status = api::Status::default();
+        ClosedForEvent
+            .modify_with_context(&mut status, &context)
+            .await
+            .unwrap();
+        assert_eq!(status.state.unwrap().message.as_deref(), Some("Party"));
+    }
+
+    struct Localized;
+
+    impl StatusModifier for Localized {
+        fn modify(&self, status: &mut api::Status) {
+            self.modify_localized(status, None);
+        }
+
+        fn modify_localized(&self, status: &mut api::Status, locale: Option<&str>) {
+            status.space = locale.unwrap_or("default").into();
+        }
+
+        fn locales(&self) -> Vec<String> {
+            vec!["de".into()]
+        }
+    }
+
+    #[tokio::test]
+    async fn status_modifier_adapter() {
+        let store = MemoryStore::new();
+        let headers = HeaderMap::new();
+        let modifier: &dyn ContextualStatusModifier = &Localized;
+        assert_eq!(modifier.available_locales(), vec!["de"]);
+
+        let mut status = api::Status::default();
+        modifier
+            .modify_with_context(&mut status, &Context::new(&store, &headers, "/"))
+            .await
+            .unwrap();
+        assert_eq!(status.space, "default");
+        let context = Context::new(&store, &headers, "/").with_locale(Some("de"));
+        modifier.modify_with_context(&mut status, &context).await.unwrap();
+        assert_eq!(status.space, "de");
+    }
+
+    #[test]
+    fn cacheable() {
+        let closure = |status: &mut api::Status| status.space.push('!');
+        let modifiers: [&dyn ContextualStatusModifier; 3] =
+            [&Localized, &closure, &crate::modifiers::StateFromPeopleNowPresent];
+        let cacheable: Vec<bool> = modifiers.iter().map(|modifier| modifier.cacheable()).collect();
+        assert_eq!(cacheable, vec![false, false, true]);
+    }
+}
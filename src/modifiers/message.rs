@@ -0,0 +1,217 @@
+//! Message templates for the built-in status modifiers.
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+/// A message template with optional plural forms and translations.
+///
+/// Placeholders like `{count}` are replaced with the values provided by the
+/// modifier using the message, see the documentation of the modifiers for
+/// the available placeholders. Counts select the plural form: `zero` for 0,
+/// `one` for 1 and `other` for all other counts. Missing forms fall back to
+/// `other`.
+///
+/// Translations are selected by the language preferred by the client, as
+/// sent in the `Accept-Language` header. Without a matching translation, the
+/// message itself is used.
+///
+/// ```
+/// use spaceapi_server::modifiers::Message;
+///
+/// let message = Message::new("{count} people here right now")
+///     .one("{count} person here right now")
+///     .translation(
+///         "de",
+///         Message::new("{count} Personen anwesend").one("{count} Person anwesend"),
+///     );
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(from = "MessageConfig")]
+pub struct Message {
+    other: String,
+    one: Option<String>,
+    zero: Option<String>,
+    translations: BTreeMap<String, Message>,
+}
+
+/// A message as written in a config file: either a plain string or a table
+/// with plural forms and translations.
+#[derive(Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+enum MessageConfig {
+    Plain(String),
+    Forms {
+        other: String,
+        one: Option<String>,
+        zero: Option<String>,
+        #[serde(default)]
+        translations: BTreeMap<String, Message>,
+    },
+}
+
+impl From<MessageConfig> for Message {
+    fn from(config: MessageConfig) -> Message {
+        match config {
+            MessageConfig::Plain(other) => Message::new(other),
+            MessageConfig::Forms {
+                other,
+                one,
+                zero,
+                translations,
+            } => Message {
+                other,
+                one,
+                zero,
+                translations,
+            },
+        }
+    }
+}
+
+impl Message {
+    /// Create a message, used for all counts unless other plural forms are
+    /// set.
+    pub fn new<S: Into<String>>(other: S) -> Message {
+        Message {
+            other: other.into(),
+            one: None,
+            zero: None,
+            translations: BTreeMap::new(),
+        }
+    }
+
+    /// Set the message for a count of 1.
+    pub fn one<S: Into<String>>(mut self, one: S) -> Self {
+        self.one = Some(one.into());
+        self
+    }
+
+    /// Set the message for a count of 0.
+    pub fn zero<S: Into<String>>(mut self, zero: S) -> Self {
+        self.zero = Some(zero.into());
+        self
+    }
+
+    /// Add a translation for the specified language, e.g. `de` or `de-CH`.
+    pub fn translation<S: Into<String>>(mut self, locale: S, message: Message) -> Self {
+        self.translations.insert(locale.into(), message);
+        self
+    }
+
+    /// Return the locales this message is translated to.
+    pub(crate) fn locales(&self) -> impl Iterator<Item = &str> {
+        self.translations.keys().map(String::as_str)
+    }
+
+    /// Return whether the message has a separate form for a count of 0.
+    pub(crate) fn has_zero(&self, locale: Option<&str>) -> bool {
+        self.localized(locale).zero.is_some()
+    }
+
+    fn localized(&self, locale: Option<&str>) -> &Message {
+        locale
+            .and_then(|locale| self.translations.get(locale))
+            .unwrap_or(self)
+    }
+
+    /// Render the message in the specified locale, replacing the
+    /// placeholders with the specified values.
+    pub(crate) fn render(&self, locale: Option<&str>, values: &[(&str, &str)]) -> String {
+        fill(&self.localized(locale).other, values)
+    }
+
+    /// Render the plural form for the specified count. The count is
+    /// available as the `{count}` placeholder.
+    pub(crate) fn render_count(&self, locale: Option<&str>, count: u64, values: &[(&str, &str)]) -> String {
+        let message = self.localized(locale);
+        let form = match count {
+            0 => message.zero.as_ref(),
+            1 => message.one.as_ref(),
+            _ => None,
+        };
+        let count = count.to_string();
+        let values: Vec<(&str, &str)> = [("count", &*count)]
+            .into_iter()
+            .chain(values.iter().copied())
+            .collect();
+        fill(form.unwrap_or(&message.other), &values)
+    }
+}
+
+/// Replace the placeholders in the message with the specified values.
+fn fill(form: &str, values: &[(&str, &str)]) -> String {
+    values.iter().fold(form.to_owned(), |message, (name, value)| {
+        message.replace(&format!("{{{}}}", name), value)
+    })
+}
+
+impl From<&str> for Message {
+    fn from(message: &str) -> Message {
+        Message::new(message)
+    }
+}
+
+impl From<String> for Message {
+    fn from(message: String) -> Message {
+        Message::new(message)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn people() -> Message {
+        Message::new("{count} people here")
+            .one("{count} person here")
+            .translation("de", Message::new("{count} Personen da").one("Eine Person da"))
+    }
+
+    #[test]
+    fn plural_forms() {
+        let message = people();
+        assert_eq!(message.render_count(None, 0, &[]), "0 people here");
+        assert_eq!(message.render_count(None, 1, &[]), "1 person here");
+        assert_eq!(message.render_count(None, 2, &[]), "2 people here");
+        assert!(!message.has_zero(None));
+        let message = message.zero("Nobody here");
+        assert_eq!(message.render_count(None, 0, &[]), "Nobody here");
+        assert!(message.has_zero(None));
+    }
+
+    #[test]
+    fn translations() {
+        let message = people();
+        assert_eq!(message.render_count(Some("de"), 1, &[]), "Eine Person da");
+        assert_eq!(message.render_count(Some("de"), 3, &[]), "3 Personen da");
+        assert_eq!(message.render_count(Some("fr"), 3, &[]), "3 people here");
+        assert_eq!(message.locales().collect::<Vec<_>>(), vec!["de"]);
+    }
+
+    #[test]
+    fn placeholders() {
+        let message = Message::new("Open until {until}, {unknown}");
+        assert_eq!(
+            message.render(None, &[("until", "22:00")]),
+            "Open until 22:00, {unknown}"
+        );
+    }
+
+    #[test]
+    fn deserialize() {
+        let message: Message = serde_json::from_str(r#""Open""#).unwrap();
+        assert_eq!(message, Message::new("Open"));
+        let message: Message = serde_json::from_str(
+            r#"{
+                "other": "{count} people here",
+                "one": "{count} person here",
+                "translations": {
+                    "de": { "other": "{count} Personen da", "one": "Eine Person da" }
+                }
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(message, people());
+    }
+}
@@ -0,0 +1,442 @@
+//! A status modifier based on a weekly opening hours schedule.
+
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use chrono::{DateTime, Datelike, NaiveDate, NaiveTime, Utc, Weekday};
+use chrono_tz::Tz;
+use serde::Deserialize;
+
+use super::{Context, ContextualStatusModifier, Message, ModifierError};
+use crate::api;
+
+/// How the schedule is combined with a state set by sensors, i.e. by the
+/// status modifiers registered before [`OpeningHours`]. A state that equals
+/// the `state.open` of the static status, see [`Context::static_open`],
+/// doesn't count as set by sensors.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Precedence {
+    /// The schedule always decides, a state set by sensors is overwritten.
+    Schedule,
+    /// A state set by sensors wins, the schedule is only used if the state
+    /// is unknown.
+    Sensors,
+    /// The space is open if the schedule or the sensors say so. The schedule
+    /// is used to close the space only if the state is unknown.
+    #[default]
+    Either,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Slot {
+    weekday: Weekday,
+    from: NaiveTime,
+    until: NaiveTime,
+}
+
+impl Slot {
+    /// Whether the slot ends on the next day.
+    fn is_overnight(&self) -> bool {
+        self.until <= self.from
+    }
+}
+
+/// The state according to the schedule.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Scheduled {
+    open: bool,
+    message: Option<String>,
+}
+
+/// This modifier sets `state.open` and `state.message` from a weekly
+/// schedule, e.g. for regular opening hours on Tuesdays and Fridays.
+///
+/// The schedule is evaluated in the specified timezone at the time of the
+/// request, see [`Context::now`]. On holidays, the space is closed
+/// regardless of the schedule. Register it with
+/// [`add_contextual_status_modifier`](../struct.SpaceapiServerBuilder.html#method.add_contextual_status_modifier).
+///
+/// Register this modifier after sensor-based modifiers like
+/// [`StateFromPeopleNowPresent`](struct.StateFromPeopleNowPresent.html), the
+/// [`Precedence`] decides which one wins.
+///
+/// ```
+/// use spaceapi_server::modifiers::{NaiveDate, NaiveTime, OpeningHours, Tz, Weekday};
+///
+/// let opening_hours = OpeningHours::new(Tz::Europe__Zurich)
+///     .open(Weekday::Tue, NaiveTime::from_hms_opt(18, 0, 0).unwrap(), NaiveTime::from_hms_opt(23, 0, 0).unwrap())
+///     .open(Weekday::Fri, NaiveTime::from_hms_opt(19, 0, 0).unwrap(), NaiveTime::from_hms_opt(2, 0, 0).unwrap())
+///     .holiday(NaiveDate::from_ymd_opt(2025, 12, 26).unwrap(), "Christmas");
+/// ```
+#[derive(Debug, Clone)]
+pub struct OpeningHours {
+    timezone: Tz,
+    slots: Vec<Slot>,
+    holidays: BTreeMap<NaiveDate, String>,
+    precedence: Precedence,
+    open_message: Message,
+    holiday_message: Message,
+}
+
+impl OpeningHours {
+    /// Create an empty schedule in the specified timezone.
+    pub fn new(timezone: Tz) -> OpeningHours {
+        OpeningHours {
+            timezone,
+            slots: vec![],
+            holidays: BTreeMap::new(),
+            precedence: Precedence::default(),
+            open_message: Message::new("Open until {until}"),
+            holiday_message: Message::new("Closed: {holiday}"),
+        }
+    }
+
+    /// Open the space every week on the specified day. If `until` is not
+    /// after `from`, the opening ends on the next day.
+    pub fn open(mut self, weekday: Weekday, from: NaiveTime, until: NaiveTime) -> Self {
+        self.slots.push(Slot { weekday, from, until });
+        self
+    }
+
+    /// Close the space on the specified date. An opening that starts on a
+    /// holiday doesn't take place.
+    pub fn holiday<S: Into<String>>(mut self, date: NaiveDate, name: S) -> Self {
+        self.holidays.insert(date, name.into());
+        self
+    }
+
+    /// Set how the schedule is combined with a state set by sensors.
+    /// Defaults to [`Precedence::Either`].
+    pub fn precedence(mut self, precedence: Precedence) -> Self {
+        self.precedence = precedence;
+        self
+    }
+
+    /// Set the message while the space is open according to the schedule.
+    /// The `{until}` placeholder is replaced with the closing time. Defaults
+    /// to "Open until {until}".
+    pub fn open_message<M: Into<Message>>(mut self, message: M) -> Self {
+        self.open_message = message.into();
+        self
+    }
+
+    /// Set the message on holidays. The `{holiday}` placeholder is replaced
+    /// with the name of the holiday. Defaults to "Closed: {holiday}".
+    pub fn holiday_message<M: Into<Message>>(mut self, message: M) -> Self {
+        self.holiday_message = message.into();
+        self
+    }
+
+    /// Return the state according to the schedule at the specified time.
+    fn scheduled_at(&self, now: DateTime<Utc>, locale: Option<&str>) -> Scheduled {
+        let local = now.with_timezone(&self.timezone);
+        let today = local.date_naive();
+        let time = local.time();
+
+        // Openings that started today or are still running from yesterday
+        let openings = self
+            .slots
+            .iter()
+            .filter(|slot| slot.weekday == today.weekday())
+            .filter(|slot| slot.from <= time && (time < slot.until || slot.is_overnight()))
+            .map(|slot| (today, slot.until))
+            .chain(today.pred_opt().into_iter().flat_map(|yesterday| {
+                self.slots
+                    .iter()
+                    .filter(move |slot| slot.weekday == yesterday.weekday())
+                    .filter(move |slot| slot.is_overnight() && time < slot.until)
+                    .map(move |slot| (yesterday, slot.until))
+            }));
+
+        let mut holiday = None;
+        for (date, until) in openings {
+            match self.holidays.get(&date) {
+                Some(name) => holiday = holiday.or(Some(name)),
+                None => {
+                    return Scheduled {
+                        open: true,
+                        message: Some(
+                            self.open_message
+                                .render(locale, &[("until", &until.format("%H:%M").to_string())]),
+                        ),
+                    }
+                }
+            }
+        }
+        Scheduled {
+            open: false,
+            message: holiday
+                .or_else(|| self.holidays.get(&today))
+                .map(|name| self.holiday_message.render(locale, &[("holiday", name)])),
+        }
+    }
+
+    fn modify_at(
+        &self,
+        status: &mut api::Status,
+        now: DateTime<Utc>,
+        locale: Option<&str>,
+        static_open: Option<bool>,
+    ) {
+        let scheduled = self.scheduled_at(now, locale);
+        let state = status.state.get_or_insert_with(Default::default);
+        let sensors_open = state.open.filter(|&open| Some(open) != static_open);
+        let apply = match (self.precedence, sensors_open) {
+            (Precedence::Schedule, _) => true,
+            (_, None) => true,
+            (Precedence::Sensors, Some(_)) => false,
+            (Precedence::Either, Some(open)) => scheduled.open && !open,
+        };
+        if apply {
+            // The message of an overridden sensor state is stale
+            if scheduled.message.is_some() || sensors_open.is_some() {
+                state.message = scheduled.message;
+            }
+            state.open = Some(scheduled.open);
+        }
+    }
+}
+
+#[async_trait]
+impl ContextualStatusModifier for OpeningHours {
+    async fn modify_with_context(
+        &self,
+        status: &mut api::Status,
+        context: &Context<'_>,
+    ) -> Result<(), ModifierError> {
+        self.modify_at(
+            status,
+            context.now().into(),
+            context.locale(),
+            context.static_open(),
+        );
+        Ok(())
+    }
+
+    fn available_locales(&self) -> Vec<String> {
+        let messages = [&self.open_message, &self.holiday_message];
+        let mut locales: Vec<String> = messages
+            .into_iter()
+            .flat_map(Message::locales)
+            .map(Into::into)
+            .collect();
+        locales.sort();
+        locales.dedup();
+        locales
+    }
+
+    fn cacheable(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+
+    fn time(hour: u32, minute: u32) -> NaiveTime {
+        NaiveTime::from_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    /// Return the specified local time in Zurich. 2024-01-02 is a Tuesday.
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        Tz::Europe__Zurich
+            .with_ymd_and_hms(2024, 1, day, hour, minute, 0)
+            .unwrap()
+            .with_timezone(&Utc)
+    }
+
+    fn schedule() -> OpeningHours {
+        OpeningHours::new(Tz::Europe__Zurich)
+            .open(Weekday::Tue, time(18, 0), time(23, 0))
+            .open(Weekday::Fri, time(19, 0), time(2, 0))
+    }
+
+    fn open(message: &str) -> Scheduled {
+        Scheduled {
+            open: true,
+            message: Some(message.into()),
+        }
+    }
+
+    fn closed(message: Option<&str>) -> Scheduled {
+        Scheduled {
+            open: false,
+            message: message.map(Into::into),
+        }
+    }
+
+    #[test]
+    fn weekly_schedule() {
+        let schedule = schedule();
+        assert_eq!(schedule.scheduled_at(at(2, 17, 59), None), closed(None));
+        assert_eq!(
+            schedule.scheduled_at(at(2, 18, 0), None),
+            open("Open until 23:00")
+        );
+        assert_eq!(schedule.scheduled_at(at(2, 23, 0), None), closed(None));
+        assert_eq!(schedule.scheduled_at(at(3, 20, 0), None), closed(None));
+    }
+
+    #[test]
+    fn overnight() {
+        let schedule = schedule();
+        assert_eq!(
+            schedule.scheduled_at(at(5, 23, 30), None),
+            open("Open until 02:00")
+        );
+        assert_eq!(
+            schedule.scheduled_at(at(6, 1, 59), None),
+            open("Open until 02:00")
+        );
+        assert_eq!(schedule.scheduled_at(at(6, 2, 0), None), closed(None));
+    }
+
+    #[test]
+    fn timezone() {
+        // 17:30 UTC is 18:30 in Zurich in winter
+        let now = Utc.with_ymd_and_hms(2024, 1, 2, 17, 30, 0).unwrap();
+        assert_eq!(schedule().scheduled_at(now, None), open("Open until 23:00"));
+    }
+
+    #[test]
+    fn holidays() {
+        let schedule = schedule()
+            .holiday(NaiveDate::from_ymd_opt(2024, 1, 2).unwrap(), "Berchtoldstag")
+            .holiday(NaiveDate::from_ymd_opt(2024, 1, 5).unwrap(), "Inventory");
+        assert_eq!(
+            schedule.scheduled_at(at(2, 10, 0), None),
+            closed(Some("Closed: Berchtoldstag"))
+        );
+        assert_eq!(
+            schedule.scheduled_at(at(2, 20, 0), None),
+            closed(Some("Closed: Berchtoldstag"))
+        );
+        assert_eq!(
+            schedule.scheduled_at(at(6, 1, 0), None),
+            closed(Some("Closed: Inventory"))
+        );
+    }
+
+    fn status(open: Option<bool>) -> api::Status {
+        api::Status {
+            state: Some(api::State {
+                open,
+                message: Some("Sensor message".into()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        }
+    }
+
+    fn modified(precedence: Precedence, open: Option<bool>, now: DateTime<Utc>) -> api::State {
+        let mut status = status(open);
+        schedule()
+            .precedence(precedence)
+            .modify_at(&mut status, now, None, None);
+        status.state.unwrap()
+    }
+
+    #[test]
+    fn precedence() {
+        let opening = at(2, 20, 0);
+        let closing = at(2, 10, 0);
+
+        // The schedule always wins
+        let state = modified(Precedence::Schedule, Some(false), opening);
+        assert_eq!(state.open, Some(true));
+        assert_eq!(state.message.as_deref(), Some("Open until 23:00"));
+        let state = modified(Precedence::Schedule, Some(true), closing);
+        assert_eq!(state.open, Some(false));
+        assert_eq!(state.message, None);
+
+        // The sensors win
+        assert_eq!(
+            modified(Precedence::Sensors, Some(false), opening).open,
+            Some(false)
+        );
+        assert_eq!(modified(Precedence::Sensors, None, opening).open, Some(true));
+        let state = modified(Precedence::Sensors, None, closing);
+        assert_eq!(state.open, Some(false));
+        assert_eq!(state.message.as_deref(), Some("Sensor message"));
+
+        // Open if either says so
+        assert_eq!(
+            modified(Precedence::Either, Some(false), opening).open,
+            Some(true)
+        );
+        let state = modified(Precedence::Either, Some(true), closing);
+        assert_eq!(state.open, Some(true));
+        assert_eq!(state.message.as_deref(), Some("Sensor message"));
+        assert_eq!(modified(Precedence::Either, None, closing).open, Some(false));
+    }
+
+    #[test]
+    fn static_state() {
+        // The state of the static status isn't set by sensors
+        let mut modified = status(Some(false));
+        schedule()
+            .precedence(Precedence::Sensors)
+            .modify_at(&mut modified, at(2, 20, 0), None, Some(false));
+        let state = modified.state.unwrap();
+        assert_eq!(state.open, Some(true));
+        assert_eq!(state.message.as_deref(), Some("Open until 23:00"));
+
+        let mut modified = status(Some(true));
+        schedule()
+            .precedence(Precedence::Sensors)
+            .modify_at(&mut modified, at(2, 10, 0), None, Some(false));
+        assert_eq!(modified.state.unwrap().open, Some(true));
+    }
+
+    #[test]
+    fn localized_messages() {
+        let schedule = schedule()
+            .holiday(NaiveDate::from_ymd_opt(2024, 1, 5).unwrap(), "Inventur")
+            .open_message(
+                Message::new("Open until {until}").translation("de", Message::new("Offen bis {until}")),
+            )
+            .holiday_message(Message::new("Closed").translation("de-CH", Message::new("Zu: {holiday}")));
+        assert_eq!(schedule.available_locales(), vec!["de", "de-CH"]);
+        assert_eq!(
+            schedule.scheduled_at(at(2, 20, 0), Some("de")),
+            open("Offen bis 23:00")
+        );
+        assert_eq!(
+            schedule.scheduled_at(at(5, 20, 0), Some("de-CH")),
+            closed(Some("Zu: Inventur"))
+        );
+        assert_eq!(schedule.scheduled_at(at(5, 20, 0), None), closed(Some("Closed")));
+    }
+
+    #[test]
+    fn no_state() {
+        let mut status = api::Status::default();
+        schedule().modify_at(&mut status, at(2, 20, 0), None, None);
+        assert_eq!(status.state.unwrap().open, Some(true));
+    }
+
+    #[tokio::test]
+    async fn context_time() {
+        let store = crate::store::MemoryStore::new();
+        let headers = axum::http::HeaderMap::new();
+        let context = Context::new(&store, &headers, "/").with_now(at(2, 20, 0).into());
+        let mut status = api::Status::default();
+        schedule()
+            .modify_with_context(&mut status, &context)
+            .await
+            .unwrap();
+        assert_eq!(status.state.unwrap().open, Some(true));
+
+        let context = Context::new(&store, &headers, "/").with_now(at(2, 10, 0).into());
+        let mut status = api::Status::default();
+        schedule()
+            .modify_with_context(&mut status, &context)
+            .await
+            .unwrap();
+        assert_eq!(status.state.unwrap().open, Some(false));
+    }
+}
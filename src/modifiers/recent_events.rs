@@ -0,0 +1,59 @@
+//! A status modifier serving the most recent events of the event log.
+
+use async_trait::async_trait;
+
+use super::{Context, ContextualStatusModifier, ModifierError};
+use crate::api;
+use crate::events;
+
+/// This modifier appends the most recent events of the
+/// [event log](../events/index.html) to the `events` field, newest first.
+/// Events from the static status are kept.
+///
+/// ```
+/// use spaceapi_server::modifiers::RecentEvents;
+///
+/// let modifier = RecentEvents::new(10);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecentEvents {
+    key: String,
+    count: usize,
+}
+
+impl RecentEvents {
+    /// Serve the specified number of events from the log at the default
+    /// store key `events`.
+    pub fn new(count: usize) -> RecentEvents {
+        RecentEvents {
+            key: "events".into(),
+            count,
+        }
+    }
+
+    /// Set the key of the list holding the events in the store, if the
+    /// event log uses another one.
+    pub fn key<S: Into<String>>(mut self, key: S) -> Self {
+        self.key = key.into();
+        self
+    }
+}
+
+#[async_trait]
+impl ContextualStatusModifier for RecentEvents {
+    async fn modify_with_context(
+        &self,
+        status: &mut api::Status,
+        context: &Context<'_>,
+    ) -> Result<(), ModifierError> {
+        let recent = events::recent(context.store(), &self.key, self.count).await?;
+        if !recent.is_empty() {
+            status.events.get_or_insert_with(Vec::new).extend(recent);
+        }
+        Ok(())
+    }
+
+    fn cacheable(&self) -> bool {
+        true
+    }
+}
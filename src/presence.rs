@@ -0,0 +1,326 @@
+//! Check-in and check-out of members, with opt-in visibility of their names.
+//!
+//! Members check in with a `PUT` request to `/presence/<name>/` and check
+//! out with a `DELETE` request to the same path, sending their own token in
+//! an `Authorization: Bearer <token>` header:
+//!
+//! ```text
+//! curl -X PUT -H "Authorization: Bearer alice-token" -d visible=true http://127.0.0.1:8000/presence/alice/
+//! curl -X PUT -H "Authorization: Bearer bob-token" http://127.0.0.1:8000/presence/bob/
+//! curl -X DELETE -H "Authorization: Bearer alice-token" http://127.0.0.1:8000/presence/alice/
+//! ```
+//!
+//! Only members with a token can check in, and only themselves. This way,
+//! nobody can check out somebody else.
+//!
+//! Only the names of members who check in with `visible=true` are listed in
+//! the `names` of the first `people_now_present` sensor. If no such sensor
+//! is registered, one is added with the number of checked in members as
+//! value. A registered sensor that can't be read from the store is omitted
+//! as usual, no sensor is made up from the presence list then.
+//! Members who forget to check out are checked out automatically after a
+//! configurable time, which is recorded in the event log like a check-out.
+//!
+//! The presence list is kept in a hash in the store, so the store must
+//! support hashes, like Redis does.
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use spaceapi_server::presence::Presence;
+//!
+//! let presence = Presence::new()
+//!     .checkout_after(Duration::from_secs(8 * 3600))
+//!     .allow_member("alice", "alice-token")
+//!     .allow_member("bob", "bob-token");
+//! ```
+
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::api::{self, sensors};
+use crate::auth;
+use crate::store::{self, Store, StoreError};
+
+/// The presence list settings, see the [module documentation](index.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Presence {
+    key: String,
+    checkout_after: Duration,
+    /// The tokens by member name.
+    members: HashMap<String, String>,
+}
+
+impl Default for Presence {
+    fn default() -> Self {
+        Presence {
+            key: "presence".into(),
+            checkout_after: Duration::from_secs(12 * 3600),
+            members: HashMap::new(),
+        }
+    }
+}
+
+/// A checked in member, as stored in the presence hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct CheckIn {
+    /// The time of the check-in, in seconds since the Unix epoch.
+    since: u64,
+    /// Whether the member agreed to show their name.
+    visible: bool,
+}
+
+impl Presence {
+    /// Create the presence list settings with the default store key
+    /// `presence` and an automatic checkout after 12 hours.
+    pub fn new() -> Presence {
+        Presence::default()
+    }
+
+    /// Set the key of the hash holding the presence list in the store.
+    pub fn key<S: Into<String>>(mut self, key: S) -> Self {
+        self.key = key.into();
+        self
+    }
+
+    /// Check out members automatically after the specified time.
+    pub fn checkout_after(mut self, checkout_after: Duration) -> Self {
+        self.checkout_after = checkout_after;
+        self
+    }
+
+    /// Let the member check in and out by sending the specified token in an
+    /// `Authorization: Bearer <token>` header. Members without a token can't
+    /// check in.
+    pub fn allow_member<N: Into<String>, S: Into<String>>(mut self, name: N, token: S) -> Self {
+        self.members.insert(name.into(), token.into());
+        self
+    }
+
+    /// Return whether a client with the specified bearer token may check the
+    /// member in and out.
+    pub(crate) fn allows(&self, name: &str, token: Option<&str>) -> bool {
+        match (self.members.get(name), token) {
+            (Some(expected), Some(token)) => auth::constant_time_eq(expected, token),
+            _ => false,
+        }
+    }
+
+    /// Check in the member.
+    pub(crate) async fn check_in(
+        &self,
+        store: &dyn Store,
+        name: &str,
+        visible: bool,
+        now: SystemTime,
+    ) -> Result<(), StoreError> {
+        let check_in = CheckIn {
+            since: store::unix_time(now),
+            visible,
+        };
+        let value = serde_json::to_string(&check_in).expect("Could not serialize check-in");
+        store.set_hash_field(&self.key, name, &value).await
+    }
+
+    /// Check out the member. Return whether the member was checked in with
+    /// a visible name.
+    pub(crate) async fn check_out(&self, store: &dyn Store, name: &str) -> Result<bool, StoreError> {
+        let visible = store
+            .get_hash(&self.key)
+            .await?
+            .get(name)
+            .and_then(|value| serde_json::from_str::<CheckIn>(value).ok())
+            .is_some_and(|check_in| check_in.visible);
+        store.delete_hash_fields(&self.key, &[name]).await?;
+        Ok(visible)
+    }
+
+    /// Return the members currently checked in, and remove the expired and
+    /// invalid check-ins from the store. Check-ins are only removed if they
+    /// are unchanged, so that a member checking in again concurrently stays
+    /// checked in.
+    ///
+    /// Also return the visible members who were checked out automatically,
+    /// with the time their check-in expired.
+    async fn checked_in(
+        &self,
+        store: &dyn Store,
+        now: SystemTime,
+    ) -> Result<(HashMap<String, CheckIn>, Vec<(String, SystemTime)>), StoreError> {
+        let now = store::unix_time(now);
+        let mut checked_in = HashMap::new();
+        let mut checked_out = vec![];
+        for (name, value) in store.get_hash(&self.key).await? {
+            let check_in = match serde_json::from_str::<CheckIn>(&value) {
+                Ok(check_in) if now.saturating_sub(check_in.since) < self.checkout_after.as_secs() => {
+                    checked_in.insert(name, check_in);
+                    continue;
+                }
+                Ok(check_in) => Some(check_in),
+                Err(e) => {
+                    warn!("Invalid check-in of \"{}\", checking out: {}", name, e);
+                    None
+                }
+            };
+            let deleted = store.delete_hash_field_if(&self.key, &name, &value).await?;
+            if let Some(check_in) = check_in.filter(|check_in| deleted && check_in.visible) {
+                let expired_at =
+                    SystemTime::UNIX_EPOCH + Duration::from_secs(check_in.since) + self.checkout_after;
+                checked_out.push((name, expired_at));
+            }
+        }
+        Ok((checked_in, checked_out))
+    }
+
+    /// Set the names of the visible members on the first people now present
+    /// sensor. If there is none and `add_sensor` is set, i.e. no people now
+    /// present sensor is registered, one is added. Return the visible
+    /// members who were checked out automatically, with the time their
+    /// check-in expired.
+    pub(crate) async fn apply(
+        &self,
+        status: &mut api::Status,
+        store: &dyn Store,
+        now: SystemTime,
+        add_sensor: bool,
+    ) -> Result<Vec<(String, SystemTime)>, StoreError> {
+        let (checked_in, checked_out) = self.checked_in(store, now).await?;
+        let mut names: Vec<String> = checked_in
+            .iter()
+            .filter(|(_, check_in)| check_in.visible)
+            .map(|(name, _)| name.clone())
+            .collect();
+        names.sort();
+        let names = Some(names).filter(|names| !names.is_empty());
+
+        let people_now_present = &mut status
+            .sensors
+            .get_or_insert_with(Default::default)
+            .people_now_present;
+        match people_now_present.first_mut() {
+            Some(sensor) => sensor.names = names,
+            None if add_sensor => people_now_present.push(sensors::PeopleNowPresentSensor {
+                value: checked_in.len() as u64,
+                names,
+                ..Default::default()
+            }),
+            None => {}
+        }
+        Ok(checked_out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::MemoryStore;
+
+    fn at(secs: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    fn people_now_present(status: &api::Status) -> &sensors::PeopleNowPresentSensor {
+        &status.sensors.as_ref().unwrap().people_now_present[0]
+    }
+
+    #[tokio::test]
+    async fn only_visible_names() {
+        let (presence, store) = (Presence::new(), MemoryStore::new());
+        presence.check_in(&store, "bob", true, at(100)).await.unwrap();
+        presence.check_in(&store, "alice", true, at(100)).await.unwrap();
+        presence
+            .check_in(&store, "mallory", false, at(100))
+            .await
+            .unwrap();
+
+        let mut status = api::Status::default();
+        presence.apply(&mut status, &store, at(200), true).await.unwrap();
+        let sensor = people_now_present(&status);
+        assert_eq!(sensor.value, 3);
+        assert_eq!(sensor.names, Some(vec!["alice".into(), "bob".into()]));
+
+        assert!(presence.check_out(&store, "alice").await.unwrap());
+        presence.check_out(&store, "bob").await.unwrap();
+        assert!(!presence.check_out(&store, "mallory").await.unwrap());
+        presence
+            .check_in(&store, "mallory", false, at(100))
+            .await
+            .unwrap();
+        let mut status = api::Status::default();
+        presence.apply(&mut status, &store, at(200), true).await.unwrap();
+        let sensor = people_now_present(&status);
+        assert_eq!(sensor.value, 1);
+        assert_eq!(sensor.names, None);
+    }
+
+    #[tokio::test]
+    async fn existing_sensor() {
+        let (presence, store) = (Presence::new(), MemoryStore::new());
+        presence.check_in(&store, "alice", true, at(100)).await.unwrap();
+        let mut status = api::Status {
+            sensors: Some(sensors::Sensors {
+                people_now_present: vec![sensors::PeopleNowPresentSensor {
+                    value: 5,
+                    ..Default::default()
+                }],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        presence.apply(&mut status, &store, at(100), false).await.unwrap();
+        let sensor = people_now_present(&status);
+        assert_eq!(sensor.value, 5);
+        assert_eq!(sensor.names, Some(vec!["alice".into()]));
+    }
+
+    #[tokio::test]
+    async fn failed_sensor_read() {
+        // A registered sensor that couldn't be read isn't made up
+        let (presence, store) = (Presence::new(), MemoryStore::new());
+        presence.check_in(&store, "alice", true, at(100)).await.unwrap();
+        let mut status = api::Status::default();
+        presence.apply(&mut status, &store, at(100), false).await.unwrap();
+        assert!(status.sensors.unwrap().people_now_present.is_empty());
+    }
+
+    #[tokio::test]
+    async fn automatic_checkout() {
+        let presence = Presence::new().checkout_after(Duration::from_secs(60));
+        let store = MemoryStore::new();
+        presence.check_in(&store, "alice", true, at(100)).await.unwrap();
+        presence.check_in(&store, "bob", true, at(150)).await.unwrap();
+        presence
+            .check_in(&store, "mallory", false, at(100))
+            .await
+            .unwrap();
+        store.set_hash_field("presence", "eve", "invalid").await.unwrap();
+
+        let mut status = api::Status::default();
+        let checked_out = presence.apply(&mut status, &store, at(170), true).await.unwrap();
+        assert_eq!(checked_out, vec![("alice".into(), at(160))]);
+        assert_eq!(people_now_present(&status).names, Some(vec!["bob".into()]));
+        let stored: Vec<String> = store.get_hash("presence").await.unwrap().into_keys().collect();
+        assert_eq!(stored, vec!["bob"]);
+
+        // Only one render reports the checkout
+        let checked_out = presence.apply(&mut status, &store, at(170), true).await.unwrap();
+        assert!(checked_out.is_empty());
+    }
+
+    #[test]
+    fn tokens() {
+        assert!(!Presence::new().allows("alice", None));
+        assert!(!Presence::new().allows("alice", Some("")));
+        let presence = Presence::new()
+            .allow_member("alice", "alice-token")
+            .allow_member("bob", "bob-token");
+        assert!(presence.allows("alice", Some("alice-token")));
+        assert!(!presence.allows("alice", Some("bob-token")));
+        assert!(!presence.allows("alice", Some("guess")));
+        assert!(!presence.allows("alice", None));
+        assert!(!presence.allows("eve", Some("alice-token")));
+    }
+}
@@ -1,23 +1,24 @@
 //! Sensor related stuff.
 
+use std::any::TypeId;
 use std::sync::Arc;
 
 use quick_error::quick_error;
-use redis::Commands;
-use redis::RedisError;
 
 use crate::api::sensors;
-use crate::types::RedisPool;
+use crate::store::{Store, StoreError};
 
 /// A specification of a sensor.
 ///
 /// The ``template`` field contains the static data of a sensor and
-/// the ``data_key`` says how to find the sensor value in Redis.
+/// the ``data_key`` says how to find the sensor value in the store.
 pub(crate) struct SensorSpec {
     /// A reference to an instantiated sensor template
     pub(crate) template: Box<dyn sensors::SensorTemplate>,
     /// The data key that is used to store and update the sensor value
     pub(crate) data_key: String,
+    /// Whether the template is a ``PeopleNowPresentSensorTemplate``
+    pub(crate) people_now_present: bool,
 }
 
 quick_error! {
@@ -32,14 +33,10 @@ quick_error! {
         UnknownSensor(err: String) {
             display("Unknown sensor: {}", err)
         }
-        /// Redis error
-        Redis(err: RedisError) {
-            from()
-            source(err)
-        }
-        /// R2d2 connection pool error
-        R2d2(err: r2d2::Error) {
+        /// Store error
+        Store(err: StoreError) {
             from()
+            display("{}", err)
             source(err)
         }
     }
@@ -49,17 +46,29 @@ quick_error! {
 pub(crate) type SafeSensorSpecs = Arc<Vec<SensorSpec>>;
 
 impl SensorSpec {
-    /// Retrieve sensor value from Redis.
-    pub(crate) fn get_sensor_value(&self, redis_pool: &RedisPool) -> Result<String, SensorError> {
-        let mut conn = redis_pool.get()?;
-        let value: String = conn.get(&*self.data_key)?;
-        Ok(value)
+    /// Create a sensor spec from the template and the data key.
+    pub(crate) fn new<T: sensors::SensorTemplate + 'static>(template: T, data_key: String) -> SensorSpec {
+        SensorSpec {
+            template: Box::new(template),
+            data_key,
+            people_now_present: TypeId::of::<T>() == TypeId::of::<sensors::PeopleNowPresentSensorTemplate>(),
+        }
     }
 
-    /// Set sensor value in Redis.
-    pub(crate) fn set_sensor_value(&self, redis_pool: &RedisPool, value: &str) -> Result<(), SensorError> {
-        let mut conn = redis_pool.get()?;
-        let _: String = conn.set(&*self.data_key, value)?;
-        Ok(())
+    /// Set sensor value in the store.
+    pub(crate) async fn set_sensor_value(&self, store: &dyn Store, value: &str) -> Result<(), SensorError> {
+        Ok(store.set_value(&self.data_key, value).await?)
     }
 }
+
+/// Retrieve the values of all sensors from the store in a single round-trip.
+///
+/// The returned values are in the same order as the ``sensor_specs``. Keys
+/// that are not set in the store result in a ``None`` value.
+pub(crate) async fn get_sensor_values(
+    sensor_specs: &[SensorSpec],
+    store: &dyn Store,
+) -> Result<Vec<Option<String>>, SensorError> {
+    let keys: Vec<&str> = sensor_specs.iter().map(|spec| &*spec.data_key).collect();
+    Ok(store.get_values(&keys).await?)
+}
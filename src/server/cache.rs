@@ -0,0 +1,194 @@
+//! Short-lived cache for the rendered status.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use axum::body::Bytes;
+
+struct Entries<T> {
+    /// The rendered bodies by key, e.g. the locale they were rendered for.
+    bodies: HashMap<String, (Instant, T)>,
+    /// The locks serializing the renders of each key. Locks are dropped
+    /// once nobody renders the key anymore.
+    render_locks: HashMap<String, Arc<tokio::sync::Mutex<()>>>,
+    /// Incremented on every invalidation, so that a render that started
+    /// before an invalidation doesn't store a stale body.
+    generation: u64,
+}
+
+impl<T> Default for Entries<T> {
+    fn default() -> Self {
+        Entries {
+            bodies: HashMap::new(),
+            render_locks: HashMap::new(),
+            generation: 0,
+        }
+    }
+}
+
+/// Caches the serialized status, i.e. the status with all sensor values and
+/// status modifiers applied, for a configurable time to live.
+///
+/// Bodies are cached separately per key, because the rendered status
+/// depends on the request, e.g. on the negotiated locale. Renders of the
+/// same key are serialized, so a burst of concurrent requests results in a
+/// single render per key, while renders of different keys run concurrently.
+pub(crate) struct ResponseCache<T = Bytes> {
+    ttl: Duration,
+    entries: Mutex<Entries<T>>,
+}
+
+impl<T: Clone> ResponseCache<T> {
+    pub(crate) fn new(ttl: Duration) -> ResponseCache<T> {
+        ResponseCache {
+            ttl,
+            entries: Mutex::new(Entries::default()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Entries<T>> {
+        // The lock is never held across a render, so a poisoned lock can
+        // safely be reused.
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Return the cached body for the key if it is still fresh.
+    fn fresh(&self, key: &str) -> Option<T> {
+        match self.lock().bodies.get(key) {
+            Some((rendered_at, body)) if rendered_at.elapsed() < self.ttl => Some(body.clone()),
+            _ => None,
+        }
+    }
+
+    /// Return the cached body for the key if it is still fresh, otherwise
+    /// render a new one. Failed renders are not cached.
+    pub(crate) async fn get_or_render<F, Fut, E>(&self, key: &str, render: F) -> Result<T, E>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T, E>>,
+    {
+        if let Some(body) = self.fresh(key) {
+            return Ok(body);
+        }
+        let render_lock = self.lock().render_locks.entry(key.into()).or_default().clone();
+        let render_guard = render_lock.lock().await;
+        // Another request may have rendered while we were waiting
+        let result = match self.fresh(key) {
+            Some(body) => Ok(body),
+            None => {
+                let generation = self.lock().generation;
+                let result = render().await;
+                let mut entries = self.lock();
+                if let (Ok(body), true) = (&result, entries.generation == generation) {
+                    // Drop expired bodies, so that rarely requested keys don't pile up
+                    let ttl = self.ttl;
+                    entries
+                        .bodies
+                        .retain(|_, (rendered_at, _)| rendered_at.elapsed() < ttl);
+                    entries.bodies.insert(key.into(), (Instant::now(), body.clone()));
+                }
+                result
+            }
+        };
+        drop(render_guard);
+        drop(render_lock);
+        // Drop the locks of keys nobody is rendering anymore
+        self.lock()
+            .render_locks
+            .retain(|_, lock| Arc::strong_count(lock) > 1);
+        result
+    }
+
+    /// Drop all cached bodies, forcing the next requests to render again.
+    pub(crate) fn invalidate(&self) {
+        let mut entries = self.lock();
+        entries.bodies.clear();
+        entries.generation += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn get(cache: &ResponseCache<String>, key: &str, body: &str) -> String {
+        cache
+            .get_or_render(key, || async { Ok::<_, ()>(body.into()) })
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn renders_once_while_fresh() {
+        let cache: ResponseCache<String> = ResponseCache::new(Duration::from_secs(60));
+        assert_eq!(get(&cache, "", "first").await, "first");
+        assert_eq!(get(&cache, "", "second").await, "first");
+    }
+
+    #[tokio::test]
+    async fn renders_again_after_ttl() {
+        let cache: ResponseCache<String> = ResponseCache::new(Duration::from_secs(0));
+        assert_eq!(get(&cache, "", "first").await, "first");
+        assert_eq!(get(&cache, "", "second").await, "second");
+    }
+
+    #[tokio::test]
+    async fn renders_again_after_invalidate() {
+        let cache: ResponseCache<String> = ResponseCache::new(Duration::from_secs(60));
+        assert_eq!(get(&cache, "", "first").await, "first");
+        cache.invalidate();
+        assert_eq!(get(&cache, "", "second").await, "second");
+    }
+
+    #[tokio::test]
+    async fn invalidate_during_render() {
+        let cache: ResponseCache<String> = ResponseCache::new(Duration::from_secs(60));
+        let body = cache
+            .get_or_render("", || async {
+                cache.invalidate();
+                Ok::<_, ()>("stale".into())
+            })
+            .await;
+        assert_eq!(body, Ok("stale".into()));
+        assert_eq!(get(&cache, "", "fresh").await, "fresh");
+    }
+
+    #[tokio::test]
+    async fn renders_per_key() {
+        let cache: ResponseCache<String> = ResponseCache::new(Duration::from_secs(60));
+        assert_eq!(get(&cache, "en", "en").await, "en");
+        assert_eq!(get(&cache, "de", "de").await, "de");
+        assert_eq!(get(&cache, "en", "second").await, "en");
+        cache.invalidate();
+        assert_eq!(get(&cache, "de", "second").await, "second");
+    }
+
+    #[tokio::test]
+    async fn renders_keys_concurrently() {
+        let cache: ResponseCache<String> = ResponseCache::new(Duration::from_secs(60));
+        let fast_rendered = tokio::sync::Notify::new();
+        let slow = cache.get_or_render("slow", || async {
+            fast_rendered.notified().await;
+            Ok::<_, ()>("slow".into())
+        });
+        let fast = async {
+            let body = get(&cache, "fast", "fast").await;
+            fast_rendered.notify_one();
+            body
+        };
+        assert_eq!(tokio::join!(slow, fast), (Ok("slow".into()), "fast".into()));
+        assert!(cache.lock().render_locks.is_empty());
+    }
+
+    #[tokio::test]
+    async fn failed_renders_not_cached() {
+        let cache: ResponseCache<String> = ResponseCache::new(Duration::from_secs(60));
+        assert_eq!(
+            cache.get_or_render("", || async { Err("failed") }).await,
+            Err("failed")
+        );
+        assert_eq!(get(&cache, "", "rendered").await, "rendered");
+    }
+}
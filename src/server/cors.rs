@@ -0,0 +1,140 @@
+//! Cross-Origin Resource Sharing (CORS) support.
+
+use axum::extract::{Request, State};
+use axum::http::header::{self, HeaderMap, HeaderValue};
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::Response;
+
+/// The CORS policy applied to all responses.
+#[derive(Debug, Clone)]
+pub(crate) struct CorsPolicy {
+    /// Allowed origins. A `*` entry allows any origin, an empty list disables
+    /// CORS.
+    pub(crate) allowed_origins: Vec<String>,
+    /// Methods allowed in preflight responses.
+    pub(crate) allowed_methods: Vec<String>,
+    /// Request headers allowed in preflight responses.
+    pub(crate) allowed_headers: Vec<String>,
+}
+
+impl Default for CorsPolicy {
+    fn default() -> Self {
+        CorsPolicy {
+            allowed_origins: vec!["*".into()],
+            allowed_methods: vec![
+                "GET".into(),
+                "POST".into(),
+                "PUT".into(),
+                "DELETE".into(),
+                "OPTIONS".into(),
+            ],
+            allowed_headers: vec!["Authorization".into(), "Content-Type".into()],
+        }
+    }
+}
+
+impl CorsPolicy {
+    fn allows_any_origin(&self) -> bool {
+        self.allowed_origins.iter().any(|o| o == "*")
+    }
+
+    /// Return the value of the `Access-Control-Allow-Origin` header for a
+    /// request with the specified `Origin` header, if the origin is allowed.
+    fn allow_origin(&self, origin: Option<&str>) -> Option<String> {
+        if self.allows_any_origin() {
+            return Some("*".into());
+        }
+        origin
+            .filter(|origin| self.allowed_origins.iter().any(|o| o == origin))
+            .map(Into::into)
+    }
+
+    fn apply(&self, origin: Option<&str>, preflight: bool, headers: &mut HeaderMap) {
+        if self.allowed_origins.is_empty() {
+            return;
+        }
+        if !self.allows_any_origin() {
+            // The response depends on the request origin
+            headers.append(header::VARY, HeaderValue::from_static("Origin"));
+        }
+        if let Some(allowed) = self.allow_origin(origin) {
+            set_header(headers, header::ACCESS_CONTROL_ALLOW_ORIGIN, &allowed);
+        }
+        if preflight {
+            set_header(
+                headers,
+                header::ACCESS_CONTROL_ALLOW_METHODS,
+                &self.allowed_methods.join(", "),
+            );
+            set_header(
+                headers,
+                header::ACCESS_CONTROL_ALLOW_HEADERS,
+                &self.allowed_headers.join(", "),
+            );
+        }
+    }
+}
+
+fn set_header(headers: &mut HeaderMap, name: header::HeaderName, value: &str) {
+    if let Ok(value) = HeaderValue::from_str(value) {
+        headers.insert(name, value);
+    }
+}
+
+/// Middleware adding the CORS headers to every response.
+pub(crate) async fn cors(State(policy): State<CorsPolicy>, req: Request, next: Next) -> Response {
+    let origin = req
+        .headers()
+        .get(header::ORIGIN)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from);
+    let preflight = req.method() == Method::OPTIONS;
+    let mut res = next.run(req).await;
+    policy.apply(origin.as_deref(), preflight, res.headers_mut());
+    res
+}
+
+/// Answers CORS preflight requests. The actual headers are added by the
+/// `cors` middleware.
+pub(crate) async fn preflight() -> StatusCode {
+    StatusCode::NO_CONTENT
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_allows_any_origin() {
+        let policy = CorsPolicy::default();
+        assert_eq!(policy.allow_origin(None), Some("*".into()));
+        assert_eq!(policy.allow_origin(Some("https://example.com")), Some("*".into()));
+    }
+
+    #[test]
+    fn restricted_origins() {
+        let policy = CorsPolicy {
+            allowed_origins: vec!["https://dashboard.example.com".into()],
+            ..CorsPolicy::default()
+        };
+        assert_eq!(
+            policy.allow_origin(Some("https://dashboard.example.com")),
+            Some("https://dashboard.example.com".into())
+        );
+        assert_eq!(policy.allow_origin(Some("https://evil.example.com")), None);
+        assert_eq!(policy.allow_origin(None), None);
+    }
+
+    #[test]
+    fn no_origins_disables_cors() {
+        let policy = CorsPolicy {
+            allowed_origins: vec![],
+            ..CorsPolicy::default()
+        };
+        assert_eq!(policy.allow_origin(Some("https://example.com")), None);
+        let mut headers = HeaderMap::new();
+        policy.apply(Some("https://example.com"), true, &mut headers);
+        assert!(headers.is_empty());
+    }
+}
@@ -0,0 +1,98 @@
+//! Configuration through environment variables.
+//!
+//! This allows container deployments to configure the server without
+//! changing any code:
+//!
+//! - `HOST` overrides the host to listen on
+//! - `PORT` overrides the port to listen on
+//! - `REDIS_URL` overrides the Redis connection string
+
+use std::env;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs};
+
+pub(crate) const HOST: &str = "HOST";
+pub(crate) const PORT: &str = "PORT";
+pub(crate) const REDIS_URL: &str = "REDIS_URL";
+
+/// Read an environment variable, treating empty values as unset.
+pub(crate) fn var(key: &str) -> Option<String> {
+    env::var(key).ok().filter(|value| !value.is_empty())
+}
+
+/// Resolve the addresses to listen on, taking the `HOST` and `PORT`
+/// environment variables into account.
+pub(crate) fn listen_addrs<S: ToSocketAddrs>(socket_addr: S) -> io::Result<Vec<SocketAddr>> {
+    override_listen_addrs(
+        socket_addr.to_socket_addrs()?.collect(),
+        var(HOST).as_deref(),
+        var(PORT).as_deref(),
+    )
+}
+
+fn override_listen_addrs(
+    addrs: Vec<SocketAddr>,
+    host: Option<&str>,
+    port: Option<&str>,
+) -> io::Result<Vec<SocketAddr>> {
+    let port = match port {
+        Some(port) => port.parse::<u16>().map_err(|_| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Invalid {} environment variable: {}", PORT, port),
+            )
+        })?,
+        None => match (host, addrs.first()) {
+            (None, _) => return Ok(addrs),
+            (Some(_), Some(addr)) => addr.port(),
+            (Some(_), None) => 0,
+        },
+    };
+    let mut addrs = match host {
+        Some(host) => (host, port).to_socket_addrs()?.collect(),
+        None => addrs,
+    };
+    for addr in &mut addrs {
+        addr.set_port(port);
+    }
+    Ok(addrs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addrs(addr: &str) -> Vec<SocketAddr> {
+        addr.to_socket_addrs().unwrap().collect()
+    }
+
+    #[test]
+    fn no_overrides() {
+        let result = override_listen_addrs(addrs("127.0.0.1:8000"), None, None).unwrap();
+        assert_eq!(result, addrs("127.0.0.1:8000"));
+    }
+
+    #[test]
+    fn override_port() {
+        let result = override_listen_addrs(addrs("127.0.0.1:8000"), None, Some("3000")).unwrap();
+        assert_eq!(result, addrs("127.0.0.1:3000"));
+    }
+
+    #[test]
+    fn override_host() {
+        let result = override_listen_addrs(addrs("127.0.0.1:8000"), Some("0.0.0.0"), None).unwrap();
+        assert_eq!(result, addrs("0.0.0.0:8000"));
+    }
+
+    #[test]
+    fn override_host_and_port() {
+        let result = override_listen_addrs(addrs("127.0.0.1:8000"), Some("0.0.0.0"), Some("80")).unwrap();
+        assert_eq!(result, addrs("0.0.0.0:80"));
+    }
+
+    #[test]
+    fn invalid_port() {
+        let result = override_listen_addrs(addrs("127.0.0.1:8000"), None, Some("http"));
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -1,16 +1,31 @@
 //! Handlers for the server.
 
-use iron::modifiers::Header;
-use iron::prelude::*;
-use iron::{headers, middleware, status};
+use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use axum::body::Bytes;
+use axum::extract::{Path, State};
+use axum::http::header::{self, HeaderValue};
+use axum::http::{HeaderMap, Method, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
 use log::{debug, error, info, warn};
-use router::Router;
 use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde_json::json;
 
+use super::cache::ResponseCache;
+use super::listener::RemoteAddr;
+use super::locale;
+use super::metrics::Metrics;
+use super::reload::{ServerState, SharedState};
+use super::versions::SchemaVersion;
 use crate::api;
-use crate::modifiers;
+use crate::events::EventLog;
+use crate::modifiers::{Context, ErrorPolicy, ModifierError};
+use crate::presence::Presence;
 use crate::sensors;
-use crate::types::RedisPool;
+use crate::store::{Store, StoreError};
+use crate::views::{self, View};
 
 #[derive(Debug)]
 struct ErrorResponse {
@@ -29,198 +44,796 @@ impl Serialize for ErrorResponse {
     }
 }
 
-pub(crate) struct ReadHandler {
-    status: api::Status,
-    redis_pool: RedisPool,
-    sensor_specs: sensors::SafeSensorSpecs,
-    status_modifiers: Vec<Box<dyn modifiers::StatusModifier>>,
+/// Build a response with the JSON content type and caching disabled.
+fn response<B: IntoResponse>(status_code: StatusCode, body: B) -> Response {
+    (
+        status_code,
+        [
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/json; charset=utf-8"),
+            ),
+            (header::CACHE_CONTROL, HeaderValue::from_static("no-cache")),
+        ],
+        body,
+    )
+        .into_response()
 }
 
-impl ReadHandler {
-    pub(crate) fn new(
-        status: api::Status,
-        redis_pool: RedisPool,
-        sensor_specs: sensors::SafeSensorSpecs,
-        status_modifiers: Vec<Box<dyn modifiers::StatusModifier>>,
-    ) -> ReadHandler {
-        ReadHandler {
-            status,
-            redis_pool,
-            sensor_specs,
-            status_modifiers,
+/// Build an error response with the specified `error_code` and the specified `reason` text.
+pub(super) fn error_response(error_code: StatusCode, reason: &str) -> Response {
+    let error = ErrorResponse {
+        reason: reason.into(),
+    };
+    let error_string = serde_json::to_string(&error).expect("Could not serialize error");
+    response(error_code, error_string)
+}
+
+/// Return the bearer token of the `Authorization` header, if any.
+pub(super) fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+/// Append an event that happened at the specified time to the log, if there
+/// is one. Failures are only logged, as the event log is secondary to the
+/// change it records.
+async fn record_event(
+    event_log: Option<&EventLog>,
+    store: &dyn Store,
+    name: &str,
+    type_: &str,
+    extra: Option<&str>,
+    time: SystemTime,
+) {
+    if let Some(event_log) = event_log {
+        if let Err(e) = event_log.record(store, name, type_, extra, time).await {
+            warn!("Could not record the {} event of \"{}\": {}", type_, name, e);
         }
     }
+}
 
-    fn build_response_json(&self) -> String {
-        // Create a mutable copy of the status struct
-        let mut status_copy = self.status.clone();
+/// Why the status could not be rendered.
+#[derive(Debug)]
+enum RenderError {
+    /// A status modifier with the ``Fail`` error policy failed.
+    Modifier,
+    /// The status can't be mapped to the requested schema version.
+    Version(&'static str),
+    /// The status doesn't match the SpaceAPI schema.
+    #[cfg(feature = "validation")]
+    Validation,
+}
 
-        // Process registered sensors
-        for sensor_spec in self.sensor_specs.iter() {
-            match sensor_spec.get_sensor_value(&self.redis_pool) {
-                // Value could be read successfullly
-                Ok(value) => {
-                    if status_copy.sensors.is_none() {
-                        status_copy.sensors = Some(api::sensors::Sensors::default());
-                    }
-                    sensor_spec
+impl IntoResponse for RenderError {
+    fn into_response(self) -> Response {
+        match self {
+            RenderError::Modifier => {
+                error_response(StatusCode::SERVICE_UNAVAILABLE, "A status modifier failed")
+            }
+            RenderError::Version(reason) => error_response(StatusCode::NOT_FOUND, reason),
+            #[cfg(feature = "validation")]
+            RenderError::Validation => error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Status does not match the SpaceAPI schema",
+            ),
+        }
+    }
+}
+
+/// Log an incoming request.
+fn log_request(method: &Method, uri: &Uri, remote_addr: RemoteAddr) {
+    match remote_addr.0 {
+        Some(addr) => info!("{} {} from {}", method, uri.path(), addr),
+        None => info!("{} {}", method, uri.path()),
+    }
+}
+
+fn count_read_failure(metrics: &Metrics, sensor_spec: &sensors::SensorSpec) {
+    metrics
+        .sensor_read_failures
+        .with_label_values(&[&sensor_spec.data_key])
+        .inc();
+}
+
+/// Add the registered sensors to the status. Return the sensors that could
+/// not be read from the store.
+async fn apply_sensors<'a>(
+    sensor_specs: &'a [sensors::SensorSpec],
+    store: &dyn Store,
+    status: &mut api::Status,
+) -> Vec<&'a sensors::SensorSpec> {
+    match sensors::get_sensor_values(sensor_specs, store).await {
+        Ok(values) => sensor_specs
+            .iter()
+            .zip(values)
+            .filter(|(sensor_spec, value)| {
+                let result = match value {
+                    // Value could be read successfully
+                    Some(value) => sensor_spec
                         .template
-                        .to_sensor(&value, status_copy.sensors.as_mut().unwrap());
-                }
+                        .try_to_sensor(value, status.sensors.get_or_insert_with(Default::default))
+                        .map_err(|e| {
+                            warn!(
+                                "Could not parse value of key '{}', omiting the sensor. Reason: {}",
+                                &sensor_spec.data_key, e
+                            )
+                        }),
 
-                // Value could not be read, do error logging
-                Err(err) => {
-                    warn!(
-                        "Could not retrieve key '{}' from Redis, omiting the sensor",
-                        &sensor_spec.data_key
-                    );
-                    match err {
-                        sensors::SensorError::Redis(e) => debug!("Error: {:?}", e),
-                        sensors::SensorError::R2d2(e) => debug!("Error: {:?}", e),
-                        sensors::SensorError::UnknownSensor(e) => warn!("Error: {:?}", e),
+                    // Value is not set
+                    None => {
+                        warn!(
+                            "Could not retrieve key '{}' from the store, omiting the sensor",
+                            &sensor_spec.data_key
+                        );
+                        Err(())
                     }
+                };
+                result.is_err()
+            })
+            .map(|(sensor_spec, _)| sensor_spec)
+            .collect(),
+
+        // Values could not be read, do error logging
+        Err(err) => {
+            warn!("Could not retrieve sensor values from the store, omiting all sensors");
+            match err {
+                sensors::SensorError::Store(e) => debug!("Error: {:?}", e),
+                sensors::SensorError::UnknownSensor(e) => warn!("Error: {:?}", e),
+            }
+            sensor_specs.iter().collect()
+        }
+    }
+}
+
+/// Build the status with all sensors and status modifiers applied. Fails
+/// if a status modifier with the ``Fail`` error policy fails.
+async fn build_status(
+    state: &ServerState,
+    store: &dyn Store,
+    metrics: &Metrics,
+    context: &Context<'_>,
+) -> Result<api::Status, ModifierError> {
+    // Create a mutable copy of the status struct
+    let mut status_copy = state.status.clone();
+
+    // Process registered sensors
+    let timer = metrics.store_latency.with_label_values(&["mget"]).start_timer();
+    let failed = apply_sensors(&state.sensor_specs, store, &mut status_copy).await;
+    timer.observe_duration();
+    for sensor_spec in failed {
+        count_read_failure(metrics, sensor_spec);
+    }
+
+    // Fill in the names of the members who checked in
+    if let Some(presence) = &state.presence {
+        let timer = metrics
+            .store_latency
+            .with_label_values(&["hgetall"])
+            .start_timer();
+        let add_sensor = !state.sensor_specs.iter().any(|spec| spec.people_now_present);
+        let result = presence
+            .apply(&mut status_copy, store, context.now(), add_sensor)
+            .await;
+        timer.observe_duration();
+        match result {
+            Ok(checked_out) => {
+                for (name, time) in checked_out {
+                    let event_log = state.event_log.as_deref();
+                    record_event(event_log, store, &name, "check-out", None, time).await;
                 }
             }
+            Err(e) => warn!("Could not retrieve the presence list from the store: {}", e),
         }
+    }
 
-        for status_modifier in &self.status_modifiers {
-            status_modifier.modify(&mut status_copy);
+    let mut modifier_errors = vec![];
+    for (status_modifier, policy) in state.status_modifiers.iter() {
+        if let Err(e) = status_modifier
+            .modify_with_context(&mut status_copy, context)
+            .await
+        {
+            warn!("Status modifier failed: {}", e);
+            match policy {
+                ErrorPolicy::Skip => {}
+                ErrorPolicy::Degrade => modifier_errors.push(json!(e.to_string())),
+                ErrorPolicy::Fail => return Err(e),
+            }
         }
+    }
+    if !modifier_errors.is_empty() {
+        status_copy
+            .extensions
+            .insert("ext_modifier_errors".into(), modifier_errors.into());
+    }
 
-        // Serialize to JSON
-        serde_json::to_string(&status_copy).expect(
+    Ok(status_copy)
+}
+
+/// Record whether the space opened or closed with an update through the HTTP
+/// API. The state is evaluated from the sensors and the cacheable status
+/// modifiers only, i.e. the ones that don't depend on the request, ignoring
+/// their errors. The presence list isn't applied, as that may check out
+/// members.
+async fn observe_state(state: &ServerState, store: &dyn Store) {
+    let event_log = match &state.event_log {
+        Some(event_log) => event_log,
+        None => return,
+    };
+    let mut status = state.status.clone();
+    apply_sensors(&state.sensor_specs, store, &mut status).await;
+    let headers = HeaderMap::new();
+    let context = Context::new(store, &headers, "/").with_static_open(state.static_open());
+    for (status_modifier, _) in state.status_modifiers.iter() {
+        if !status_modifier.cacheable() {
+            continue;
+        }
+        if let Err(e) = status_modifier.modify_with_context(&mut status, &context).await {
+            debug!("Status modifier failed while recording the state: {}", e);
+        }
+    }
+    let open = status.state.and_then(|state| state.open);
+    if let Err(e) = event_log.observe_state(store, open, context.now()).await {
+        warn!("Could not record the state change: {}", e);
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct ReadHandler {
+    state: SharedState,
+    store: Arc<dyn Store>,
+    response_cache: Option<Arc<ResponseCache>>,
+    metrics: Arc<Metrics>,
+    version: Option<SchemaVersion>,
+    #[cfg(feature = "validation")]
+    validation: crate::ValidationMode,
+}
+
+impl ReadHandler {
+    pub(crate) fn new(
+        state: SharedState,
+        store: Arc<dyn Store>,
+        response_cache: Option<Arc<ResponseCache>>,
+        metrics: Arc<Metrics>,
+    ) -> ReadHandler {
+        ReadHandler {
+            state,
+            store,
+            response_cache,
+            metrics,
+            version: None,
+            #[cfg(feature = "validation")]
+            validation: crate::ValidationMode::Off,
+        }
+    }
+
+    /// Validate the served status with the specified mode.
+    #[cfg(feature = "validation")]
+    pub(crate) fn with_validation(mut self, validation: crate::ValidationMode) -> ReadHandler {
+        self.validation = validation;
+        self
+    }
+
+    /// Serve the status mapped to the specified schema version instead of
+    /// the version it was built with.
+    pub(crate) fn with_version(mut self, version: SchemaVersion) -> ReadHandler {
+        self.version = Some(version);
+        self
+    }
+
+    /// Serialize the status as it is served through the view.
+    fn to_json(status: &api::Status, view: Option<&View>) -> serde_json::Value {
+        let mut status = serde_json::to_value(status).expect(
             "Status object could not be serialized to JSON. \
              Please open an issue at https://github.com/spaceapi-community/spaceapi-server-rs/issues",
-        )
+        );
+        if let Some(view) = view {
+            view.filter(&mut status);
+        }
+        status
+    }
+
+    /// Render the status as it is served through the view, mapped to the
+    /// requested version and serialized.
+    async fn render(
+        &self,
+        state: &ServerState,
+        context: &Context<'_>,
+        view: Option<&View>,
+    ) -> Result<Bytes, RenderError> {
+        let mut status = build_status(state, &*self.store, &self.metrics, context)
+            .await
+            .map_err(|_| RenderError::Modifier)?;
+
+        // Map the status to the requested version
+        if let Some(version) = self.version {
+            version.convert(&mut status).map_err(RenderError::Version)?;
+        }
+
+        // Serialize to JSON and validate what is actually sent
+        let json = Self::to_json(&status, view);
+        #[cfg(feature = "validation")]
+        if self.validation.check(&json).is_err() {
+            return Err(RenderError::Validation);
+        }
+        Ok(json.to_string().into())
     }
-}
 
-impl middleware::Handler for ReadHandler {
     /// Return the current status JSON.
-    fn handle(&self, req: &mut Request<'_, '_>) -> IronResult<Response> {
-        info!("{} /{} from {}", req.method, req.url.path()[0], req.remote_addr);
+    pub(crate) async fn handle(
+        State(handler): State<ReadHandler>,
+        method: Method,
+        uri: Uri,
+        remote_addr: RemoteAddr,
+        headers: HeaderMap,
+    ) -> Response {
+        log_request(&method, &uri, remote_addr);
 
-        // Get response body
-        let body = self.build_response_json();
+        // Pick the locale of the messages from the ones the modifiers provide
+        let state = handler.state.current();
+        let locales = state.locales();
+        let locale = headers
+            .get(header::ACCEPT_LANGUAGE)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| locale::negotiate(value, &locales, &state.default_locale));
 
-        // Create response
-        let response = Response::with((status::Ok, body))
-            // Set headers
-            .set(Header(headers::ContentType(
-                "application/json; charset=utf-8".parse().unwrap(),
-            )))
-            .set(Header(headers::CacheControl(vec![
-                headers::CacheDirective::NoCache,
-            ])))
-            .set(Header(headers::AccessControlAllowOrigin::Any));
+        // Select the view by the client address and bearer token
+        let token = bearer_token(&headers);
+        let view = views::select(&state.views, remote_addr.0.map(|addr| addr.ip()), token);
+
+        // Get the status with all sensors and modifiers applied. The cache
+        // is skipped if a modifier depends on more than the cache key.
+        let render = || async {
+            let context = Context::new(&*handler.store, &headers, uri.path())
+                .with_remote_addr(remote_addr.0)
+                .with_locale(locale)
+                .with_view(view.map(View::name))
+                .with_static_open(state.static_open());
+            handler.render(&state, &context, view).await
+        };
+        let cacheable = state
+            .status_modifiers
+            .iter()
+            .all(|(modifier, _)| modifier.cacheable());
+        let body = match &handler.response_cache {
+            Some(cache) if cacheable => {
+                let key = format!(
+                    "{}:{}:{}",
+                    uri.path(),
+                    view.map(View::name).unwrap_or_default(),
+                    locale.unwrap_or_default()
+                );
+                cache.get_or_render(&key, render).await
+            }
+            _ => render().await,
+        };
+        let body = match body {
+            Ok(body) => body,
+            Err(e) => return e.into_response(),
+        };
 
-        Ok(response)
+        let mut response = response(StatusCode::OK, body);
+        if !state.views.is_empty() {
+            response
+                .headers_mut()
+                .append(header::VARY, HeaderValue::from_static("Authorization"));
+        }
+        if !locales.is_empty() {
+            let headers = response.headers_mut();
+            headers.append(header::VARY, HeaderValue::from_static("Accept-Language"));
+            if let Some(value) = locale.and_then(|locale| HeaderValue::from_str(locale).ok()) {
+                headers.insert(header::CONTENT_LANGUAGE, value);
+            }
+        }
+        response
     }
 }
 
+#[derive(Clone)]
 pub(crate) struct UpdateHandler {
-    redis_pool: RedisPool,
-    sensor_specs: sensors::SafeSensorSpecs,
+    state: SharedState,
+    store: Arc<dyn Store>,
+    response_cache: Option<Arc<ResponseCache>>,
+    metrics: Arc<Metrics>,
 }
 
 impl UpdateHandler {
-    pub(crate) fn new(redis_pool: RedisPool, sensor_specs: sensors::SafeSensorSpecs) -> UpdateHandler {
+    pub(crate) fn new(
+        state: SharedState,
+        store: Arc<dyn Store>,
+        response_cache: Option<Arc<ResponseCache>>,
+        metrics: Arc<Metrics>,
+    ) -> UpdateHandler {
         UpdateHandler {
-            redis_pool,
-            sensor_specs,
+            state,
+            store,
+            response_cache,
+            metrics,
         }
     }
 
-    /// Update sensor value in Redis
-    fn update_sensor(&self, sensor: &str, value: &str) -> Result<(), sensors::SensorError> {
+    /// Update sensor value in the store
+    async fn update_sensor(&self, sensor: &str, value: &str) -> Result<(), sensors::SensorError> {
         // Validate sensor
-        let sensor_spec = self
+        let state = self.state.current();
+        let sensor_spec = state
             .sensor_specs
             .iter()
             .find(|&spec| spec.data_key == sensor)
             .ok_or_else(|| sensors::SensorError::UnknownSensor(sensor.into()))?;
 
         // Store data
-        sensor_spec.set_sensor_value(&self.redis_pool, value)
+        let timer = self
+            .metrics
+            .store_latency
+            .with_label_values(&["set"])
+            .start_timer();
+        let result = sensor_spec.set_sensor_value(&*self.store, value).await;
+        timer.observe_duration();
+        result?;
+        self.metrics
+            .sensor_updates
+            .with_label_values(&[&sensor_spec.data_key])
+            .inc();
+        let event_log = state
+            .event_log
+            .as_deref()
+            .filter(|event_log| event_log.records_sensor(&sensor_spec.data_key));
+        record_event(
+            event_log,
+            &*self.store,
+            &sensor_spec.data_key,
+            "sensor-update",
+            Some(value),
+            SystemTime::now(),
+        )
+        .await;
+        observe_state(&state, &*self.store).await;
+
+        // Make sure the next read sees the new value
+        if let Some(cache) = &self.response_cache {
+            cache.invalidate();
+        }
+        Ok(())
     }
 
     /// Build an OK response with the `HTTP 204 No Content` status code.
-    fn ok_response(&self) -> Response {
-        Response::with(status::NoContent)
-            // Set headers
-            .set(Header(headers::ContentType(
-                "application/json; charset=utf-8".parse().unwrap(),
-            )))
-            .set(Header(headers::CacheControl(vec![
-                headers::CacheDirective::NoCache,
-            ])))
-            .set(Header(headers::AccessControlAllowOrigin::Any))
+    fn ok_response() -> Response {
+        response(StatusCode::NO_CONTENT, String::new())
     }
 
     /// Build an error response with the specified `error_code` and the specified `reason` text.
-    fn err_response(&self, error_code: status::Status, reason: &str) -> Response {
-        let error = ErrorResponse {
-            reason: reason.into(),
-        };
-        let error_string = serde_json::to_string(&error).expect("Could not serialize error");
-        Response::with((error_code, error_string))
-            // Set headers
-            .set(Header(headers::ContentType(
-                "application/json; charset=utf-8".parse().unwrap(),
-            )))
-            .set(Header(headers::CacheControl(vec![
-                headers::CacheDirective::NoCache,
-            ])))
-            .set(Header(headers::AccessControlAllowOrigin::Any))
+    fn err_response(error_code: StatusCode, reason: &str) -> Response {
+        error_response(error_code, reason)
     }
-}
 
-impl middleware::Handler for UpdateHandler {
     /// Update the sensor, return correct status code.
-    fn handle(&self, req: &mut Request<'_, '_>) -> IronResult<Response> {
-        // TODO: create macro for these info! invocations.
-        info!("{} /{} from {}", req.method, req.url.path()[0], req.remote_addr);
-
-        // Get sensor name
-        let sensor_name;
-        {
-            // TODO: Properly propagate errors
-            let params = req.extensions.get::<Router>().unwrap();
-            sensor_name = params.find("sensor").unwrap().to_string();
-        }
+    pub(crate) async fn handle(
+        State(handler): State<UpdateHandler>,
+        Path(sensor_name): Path<String>,
+        method: Method,
+        uri: Uri,
+        remote_addr: RemoteAddr,
+        body: Bytes,
+    ) -> Response {
+        log_request(&method, &uri, remote_addr);
 
         // Get sensor value
-        let sensor_value;
-        {
-            let params = req.get_ref::<urlencoded::UrlEncodedBody>().unwrap();
-            sensor_value = match params.get("value") {
-                Some(values) => match values.len() {
-                    1 => values[0].to_string(),
-                    _ => return Ok(self.err_response(status::BadRequest, "Too many values specified")),
-                },
-                None => return Ok(self.err_response(status::BadRequest, "\"value\" parameter not specified")),
+        let params: Vec<(String, String)> = match serde_urlencoded::from_bytes(&body) {
+            Ok(params) => params,
+            Err(_) => return Self::err_response(StatusCode::BAD_REQUEST, "Invalid request body"),
+        };
+        let mut values = params.into_iter().filter(|(key, _)| key == "value");
+        let sensor_value = match (values.next(), values.next()) {
+            (Some((_, value)), None) => value,
+            (Some(_), Some(_)) => {
+                return Self::err_response(StatusCode::BAD_REQUEST, "Too many values specified")
             }
-        }
+            (None, _) => {
+                return Self::err_response(StatusCode::BAD_REQUEST, "\"value\" parameter not specified")
+            }
+        };
 
-        // Update values in Redis
-        if let Err(e) = self.update_sensor(&sensor_name, &sensor_value) {
+        // Update values in the store
+        if let Err(e) = handler.update_sensor(&sensor_name, &sensor_value).await {
             error!(
                 "Updating sensor value for sensor \"{}\" failed: {:?}",
                 &sensor_name, e
             );
-            let response = match e {
+            return match e {
                 sensors::SensorError::UnknownSensor(sensor) => {
-                    self.err_response(status::BadRequest, &format!("Unknown sensor: {}", sensor))
-                }
-                sensors::SensorError::Redis(_) | sensors::SensorError::R2d2(_) => {
-                    self.err_response(status::InternalServerError, "Updating values in datastore failed")
+                    Self::err_response(StatusCode::BAD_REQUEST, &format!("Unknown sensor: {}", sensor))
                 }
+                sensors::SensorError::Store(_) => Self::err_response(
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "Updating values in datastore failed",
+                ),
             };
-            return Ok(response);
         };
 
         // Create response
-        Ok(self.ok_response())
+        Self::ok_response()
+    }
+}
+
+/// Build a JSON response with the specified status code.
+fn json_response(status_code: StatusCode, body: &serde_json::Value) -> Response {
+    response(status_code, body.to_string())
+}
+
+/// Liveness check: Reports that the process is up and serving requests.
+pub(crate) async fn health() -> Response {
+    json_response(StatusCode::OK, &json!({ "status": "ok" }))
+}
+
+#[derive(Clone)]
+pub(crate) struct PresenceHandler {
+    state: SharedState,
+    store: Arc<dyn Store>,
+    response_cache: Option<Arc<ResponseCache>>,
+    metrics: Arc<Metrics>,
+}
+
+impl PresenceHandler {
+    pub(crate) fn new(
+        state: SharedState,
+        store: Arc<dyn Store>,
+        response_cache: Option<Arc<ResponseCache>>,
+        metrics: Arc<Metrics>,
+    ) -> PresenceHandler {
+        PresenceHandler {
+            state,
+            store,
+            response_cache,
+            metrics,
+        }
+    }
+
+    /// Check the token and run the update of the presence list, return the
+    /// correct status code. Check-ins and check-outs of visible members are
+    /// recorded in the event log.
+    async fn update<F, Fut>(&self, name: &str, headers: &HeaderMap, operation: &str, update: F) -> Response
+    where
+        F: FnOnce(Arc<Presence>, Option<Arc<EventLog>>) -> Fut,
+        Fut: Future<Output = Result<(), StoreError>>,
+    {
+        let state = self.state.current();
+        let presence = match &state.presence {
+            Some(presence) => presence.clone(),
+            None => return error_response(StatusCode::NOT_FOUND, "The presence list is not enabled"),
+        };
+        if !presence.allows(name, bearer_token(headers)) {
+            return error_response(StatusCode::UNAUTHORIZED, "Missing or invalid token");
+        }
+
+        let timer = self
+            .metrics
+            .store_latency
+            .with_label_values(&[operation])
+            .start_timer();
+        let result = update(presence, state.event_log.clone()).await;
+        timer.observe_duration();
+        if let Err(e) = result {
+            error!("Updating the presence list failed: {}", e);
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Updating presence in datastore failed",
+            );
+        }
+
+        // Make sure the next read sees the new presence list
+        if let Some(cache) = &self.response_cache {
+            cache.invalidate();
+        }
+        response(StatusCode::NO_CONTENT, String::new())
+    }
+
+    /// Check in a member.
+    pub(crate) async fn check_in(
+        State(handler): State<PresenceHandler>,
+        Path(name): Path<String>,
+        method: Method,
+        uri: Uri,
+        remote_addr: RemoteAddr,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Response {
+        log_request(&method, &uri, remote_addr);
+
+        let params: Vec<(String, String)> = match serde_urlencoded::from_bytes(&body) {
+            Ok(params) => params,
+            Err(_) => return error_response(StatusCode::BAD_REQUEST, "Invalid request body"),
+        };
+        let visible = match params.iter().find(|(key, _)| key == "visible") {
+            None => false,
+            Some((_, value)) => match value.parse() {
+                Ok(visible) => visible,
+                Err(_) => return error_response(StatusCode::BAD_REQUEST, "Invalid \"visible\" parameter"),
+            },
+        };
+
+        let (name, store) = (name.as_str(), &*handler.store);
+        handler
+            .update(name, &headers, "hset", |presence, event_log| async move {
+                presence.check_in(store, name, visible, SystemTime::now()).await?;
+                if visible {
+                    record_event(
+                        event_log.as_deref(),
+                        store,
+                        name,
+                        "check-in",
+                        None,
+                        SystemTime::now(),
+                    )
+                    .await;
+                }
+                Ok(())
+            })
+            .await
+    }
+
+    /// Check out a member.
+    pub(crate) async fn check_out(
+        State(handler): State<PresenceHandler>,
+        Path(name): Path<String>,
+        method: Method,
+        uri: Uri,
+        remote_addr: RemoteAddr,
+        headers: HeaderMap,
+    ) -> Response {
+        log_request(&method, &uri, remote_addr);
+
+        let (name, store) = (name.as_str(), &*handler.store);
+        handler
+            .update(name, &headers, "hdel", |presence, event_log| async move {
+                if presence.check_out(store, name).await? {
+                    record_event(
+                        event_log.as_deref(),
+                        store,
+                        name,
+                        "check-out",
+                        None,
+                        SystemTime::now(),
+                    )
+                    .await;
+                }
+                Ok(())
+            })
+            .await
+    }
+}
+
+#[derive(Clone)]
+pub(crate) struct EventsHandler {
+    state: SharedState,
+    store: Arc<dyn Store>,
+    response_cache: Option<Arc<ResponseCache>>,
+    metrics: Arc<Metrics>,
+}
+
+impl EventsHandler {
+    pub(crate) fn new(
+        state: SharedState,
+        store: Arc<dyn Store>,
+        response_cache: Option<Arc<ResponseCache>>,
+        metrics: Arc<Metrics>,
+    ) -> EventsHandler {
+        EventsHandler {
+            state,
+            store,
+            response_cache,
+            metrics,
+        }
+    }
+
+    /// Append an event to the log, return correct status code.
+    pub(crate) async fn handle(
+        State(handler): State<EventsHandler>,
+        method: Method,
+        uri: Uri,
+        remote_addr: RemoteAddr,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Response {
+        log_request(&method, &uri, remote_addr);
+
+        let state = handler.state.current();
+        let event_log = match &state.event_log {
+            Some(event_log) => event_log,
+            None => return error_response(StatusCode::NOT_FOUND, "The event log is not enabled"),
+        };
+        if !event_log.allows(bearer_token(&headers)) {
+            return error_response(StatusCode::UNAUTHORIZED, "Missing or invalid token");
+        }
+
+        let params: Vec<(String, String)> = match serde_urlencoded::from_bytes(&body) {
+            Ok(params) => params,
+            Err(_) => return error_response(StatusCode::BAD_REQUEST, "Invalid request body"),
+        };
+        let param = |name: &str| {
+            params
+                .iter()
+                .find(|(key, value)| key == name && !value.is_empty())
+                .map(|(_, value)| value.as_str())
+        };
+        let (name, type_) = match (param("name"), param("type")) {
+            (Some(name), Some(type_)) => (name, type_),
+            (None, _) => return error_response(StatusCode::BAD_REQUEST, "\"name\" parameter not specified"),
+            (_, None) => return error_response(StatusCode::BAD_REQUEST, "\"type\" parameter not specified"),
+        };
+
+        let timer = handler
+            .metrics
+            .store_latency
+            .with_label_values(&["lpush"])
+            .start_timer();
+        let result = event_log
+            .record(&*handler.store, name, type_, param("extra"), SystemTime::now())
+            .await;
+        timer.observe_duration();
+        if let Err(e) = result {
+            error!("Recording the {} event of \"{}\" failed: {}", type_, name, e);
+            return error_response(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "Recording event in datastore failed",
+            );
+        }
+
+        // Make sure the next read sees the new event
+        if let Some(cache) = &handler.response_cache {
+            cache.invalidate();
+        }
+        response(StatusCode::NO_CONTENT, String::new())
+    }
+}
+
+/// How long the readiness check waits for the store to answer a ping.
+const READINESS_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Readiness check: Reports whether the sensor store is reachable.
+#[derive(Clone)]
+pub(crate) struct ReadinessHandler {
+    store: Arc<dyn Store>,
+    metrics: Arc<Metrics>,
+}
+
+impl ReadinessHandler {
+    pub(crate) fn new(store: Arc<dyn Store>, metrics: Arc<Metrics>) -> ReadinessHandler {
+        ReadinessHandler { store, metrics }
+    }
+
+    pub(crate) async fn handle(State(handler): State<ReadinessHandler>) -> Response {
+        let timer = handler
+            .metrics
+            .store_latency
+            .with_label_values(&["ping"])
+            .start_timer();
+        let result = tokio::time::timeout(READINESS_TIMEOUT, handler.store.ping())
+            .await
+            .unwrap_or(Err(StoreError::Timeout));
+        timer.observe_duration();
+        match result {
+            Ok(()) => json_response(
+                StatusCode::OK,
+                &json!({
+                    "status": "ok",
+                    "checks": { "store": { "status": "ok" } },
+                }),
+            ),
+            Err(e) => {
+                warn!("Readiness check failed: {}", e);
+                json_response(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    &json!({
+                        "status": "error",
+                        "checks": { "store": { "status": "error", "reason": e.to_string() } },
+                    }),
+                )
+            }
+        }
     }
 }
 
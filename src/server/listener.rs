@@ -0,0 +1,49 @@
+//! Information about the connected client.
+
+use std::convert::Infallible;
+use std::net::SocketAddr;
+
+use axum::extract::connect_info::Connected;
+use axum::extract::{ConnectInfo, FromRequestParts};
+use axum::http::request::Parts;
+use axum::serve::IncomingStream;
+use tokio::net::TcpListener;
+
+/// The address of the connected client, if known.
+///
+/// This is used as connect info by all listeners of the server. When the
+/// routes are embedded into another application, its `SocketAddr` connect
+/// info is used instead, if available.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct RemoteAddr(pub(crate) Option<SocketAddr>);
+
+impl Connected<IncomingStream<'_, TcpListener>> for RemoteAddr {
+    fn connect_info(stream: IncomingStream<'_, TcpListener>) -> Self {
+        RemoteAddr(Some(*stream.remote_addr()))
+    }
+}
+
+#[cfg(unix)]
+impl Connected<IncomingStream<'_, tokio::net::UnixListener>> for RemoteAddr {
+    fn connect_info(_stream: IncomingStream<'_, tokio::net::UnixListener>) -> Self {
+        // Clients connected through a Unix socket have no IP address
+        RemoteAddr(None)
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for RemoteAddr {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let extensions = &parts.extensions;
+        Ok(extensions
+            .get::<ConnectInfo<RemoteAddr>>()
+            .map(|ConnectInfo(remote_addr)| *remote_addr)
+            .or_else(|| {
+                extensions
+                    .get::<ConnectInfo<SocketAddr>>()
+                    .map(|ConnectInfo(addr)| RemoteAddr(Some(*addr)))
+            })
+            .unwrap_or_default())
+    }
+}
@@ -0,0 +1,102 @@
+//! Negotiation of the locale of the status messages.
+
+/// Return the locale to render the status in, given the value of the
+/// `Accept-Language` header, the locales the status modifiers provide and
+/// the locale of their default messages.
+///
+/// The languages are tried in the order of their quality values. A language
+/// matches a locale with the same tag (ignoring case) or, failing that, a
+/// locale with the same primary language, e.g. `de-AT` matches `de` and
+/// `de-CH`. The default locale is matched last, so that a translation for a
+/// region is still preferred. Returns `None` to render the default messages.
+pub(crate) fn negotiate<'a>(
+    accept_language: &str,
+    available: &'a [String],
+    default_locale: &str,
+) -> Option<&'a str> {
+    let mut languages: Vec<(&str, f32)> = accept_language
+        .split(',')
+        .filter_map(|language| {
+            let mut parts = language.split(';').map(str::trim);
+            let tag = parts.next().filter(|tag| !tag.is_empty())?;
+            let quality = parts
+                .find_map(|param| param.strip_prefix("q="))
+                .map_or(Some(1.0), |q| q.parse().ok())?;
+            Some((tag, quality))
+        })
+        .filter(|(_, quality)| *quality > 0.0)
+        .collect();
+    // The sort is stable, so languages with equal quality keep their order
+    languages.sort_by(|(_, a), (_, b)| b.total_cmp(a));
+
+    for (tag, _) in languages {
+        if tag == "*" {
+            return None;
+        }
+        if let Some(locale) = available.iter().find(|locale| locale.eq_ignore_ascii_case(tag)) {
+            return Some(locale);
+        }
+        let primary = primary_language(tag);
+        if let Some(locale) = available
+            .iter()
+            .find(|locale| primary_language(locale).eq_ignore_ascii_case(primary))
+        {
+            return Some(locale);
+        }
+        if primary_language(default_locale).eq_ignore_ascii_case(primary) {
+            return None;
+        }
+    }
+    None
+}
+
+fn primary_language(tag: &str) -> &str {
+    tag.split('-').next().unwrap_or(tag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn available() -> Vec<String> {
+        vec!["de".into(), "fr-CH".into()]
+    }
+
+    #[test]
+    fn exact_match() {
+        assert_eq!(negotiate("de", &available(), "en"), Some("de"));
+        assert_eq!(negotiate("FR-ch", &available(), "en"), Some("fr-CH"));
+    }
+
+    #[test]
+    fn primary_language_match() {
+        assert_eq!(negotiate("de-AT", &available(), "en"), Some("de"));
+        assert_eq!(negotiate("fr", &available(), "en"), Some("fr-CH"));
+    }
+
+    #[test]
+    fn quality_values() {
+        assert_eq!(negotiate("de;q=0.5, fr;q=0.8", &available(), "en"), Some("fr-CH"));
+        assert_eq!(negotiate("en, de;q=0.9", &available(), "en"), None);
+        assert_eq!(negotiate("en-GB, de;q=0.9", &available(), "en"), None);
+        assert_eq!(negotiate("it, de;q=0", &available(), "en"), None);
+        assert_eq!(negotiate("fr;q=abc, de", &available(), "en"), Some("de"));
+    }
+
+    #[test]
+    fn default_messages() {
+        assert_eq!(negotiate("", &available(), "en"), None);
+        assert_eq!(negotiate("en-US, en", &available(), "en"), None);
+        assert_eq!(negotiate("*, de;q=0.5", &available(), "en"), None);
+    }
+
+    #[test]
+    fn default_locale() {
+        // Translations are preferred over the default messages of the
+        // same language
+        assert_eq!(negotiate("fr", &available(), "fr"), Some("fr-CH"));
+        assert_eq!(negotiate("fr-FR, de;q=0.9", &available(), "fr"), Some("fr-CH"));
+        assert_eq!(negotiate("it, de;q=0.9", &available(), "it"), None);
+        assert_eq!(negotiate("en, de;q=0.9", &available(), "it"), Some("de"));
+    }
+}
@@ -0,0 +1,272 @@
+//! Prometheus metrics.
+
+use std::sync::Arc;
+
+use axum::extract::{Request, State};
+use axum::http::header::{self, HeaderValue};
+use axum::http::{HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use log::{debug, error};
+use prometheus::{
+    Encoder, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
+};
+
+use super::handlers::{bearer_token, error_response};
+use super::listener::RemoteAddr;
+use super::reload::SharedState;
+use crate::sensors::{self, SensorSpec};
+use crate::store::Store;
+use crate::views::{self, View};
+
+/// All metrics exported by the server.
+pub(crate) struct Metrics {
+    registry: Registry,
+    /// HTTP requests by route and status code
+    pub(crate) requests: IntCounterVec,
+    /// Latency of HTTP requests by route
+    request_latency: HistogramVec,
+    /// Failed sensor reads by data key
+    pub(crate) sensor_read_failures: IntCounterVec,
+    /// Successful sensor updates by data key
+    pub(crate) sensor_updates: IntCounterVec,
+    /// Latency of store operations by operation
+    pub(crate) store_latency: HistogramVec,
+    /// Current numeric sensor values by data key
+    sensor_values: GaugeVec,
+}
+
+impl Metrics {
+    pub(crate) fn new() -> Metrics {
+        let requests = IntCounterVec::new(
+            Opts::new("spaceapi_http_requests_total", "Number of HTTP requests"),
+            &["route", "code"],
+        )
+        .unwrap();
+        let request_latency = HistogramVec::new(
+            HistogramOpts::new(
+                "spaceapi_http_request_duration_seconds",
+                "Latency of HTTP requests",
+            ),
+            &["route"],
+        )
+        .unwrap();
+        let sensor_read_failures = IntCounterVec::new(
+            Opts::new(
+                "spaceapi_sensor_read_failures_total",
+                "Number of sensor values that could not be read",
+            ),
+            &["data_key"],
+        )
+        .unwrap();
+        let sensor_updates = IntCounterVec::new(
+            Opts::new("spaceapi_sensor_updates_total", "Number of sensor value updates"),
+            &["data_key"],
+        )
+        .unwrap();
+        let store_latency = HistogramVec::new(
+            HistogramOpts::new(
+                "spaceapi_store_latency_seconds",
+                "Latency of sensor store operations",
+            ),
+            &["operation"],
+        )
+        .unwrap();
+        let sensor_values = GaugeVec::new(
+            Opts::new("spaceapi_sensor_value", "Current value of numeric sensors"),
+            &["data_key"],
+        )
+        .unwrap();
+
+        let registry = Registry::new();
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry.register(Box::new(request_latency.clone())).unwrap();
+        registry.register(Box::new(sensor_read_failures.clone())).unwrap();
+        registry.register(Box::new(sensor_updates.clone())).unwrap();
+        registry.register(Box::new(store_latency.clone())).unwrap();
+        registry.register(Box::new(sensor_values.clone())).unwrap();
+
+        Metrics {
+            registry,
+            requests,
+            request_latency,
+            sensor_read_failures,
+            sensor_updates,
+            store_latency,
+            sensor_values,
+        }
+    }
+
+    /// Update the sensor value gauges. Non-numeric and missing values are removed.
+    pub(crate) fn set_sensor_values(&self, sensor_specs: &[SensorSpec], values: &[Option<String>]) {
+        for (sensor_spec, value) in sensor_specs.iter().zip(values) {
+            let labels = [&*sensor_spec.data_key];
+            match value.as_deref().and_then(|v| v.trim().parse::<f64>().ok()) {
+                Some(value) => self.sensor_values.with_label_values(&labels).set(value),
+                None => {
+                    // The gauge may not exist yet, that's fine
+                    let _ = self.sensor_values.remove_label_values(&labels);
+                }
+            }
+        }
+    }
+
+    /// Remove the series of all sensors, e.g. after the sensors were
+    /// reloaded, so that removed sensors aren't exported anymore. The
+    /// counters of the remaining sensors start over, which Prometheus
+    /// handles like a restart.
+    pub(crate) fn reset_sensors(&self) {
+        self.sensor_read_failures.reset();
+        self.sensor_updates.reset();
+        self.sensor_values.reset();
+    }
+
+    /// Render all metrics in the Prometheus text format.
+    fn encode(&self) -> String {
+        let mut buffer = vec![];
+        if let Err(e) = TextEncoder::new().encode(&self.registry.gather(), &mut buffer) {
+            error!("Could not encode metrics: {}", e);
+        }
+        String::from_utf8(buffer).expect("Prometheus text format is not valid UTF-8")
+    }
+}
+
+/// Identifies the route whose requests are counted by ``count_requests``.
+#[derive(Clone)]
+pub(crate) struct Instrumented {
+    route: &'static str,
+    metrics: Arc<Metrics>,
+}
+
+impl Instrumented {
+    pub(crate) fn new(route: &'static str, metrics: Arc<Metrics>) -> Instrumented {
+        Instrumented { route, metrics }
+    }
+}
+
+/// Middleware counting the requests of a route by status code and
+/// measuring their latency.
+pub(crate) async fn count_requests(
+    State(instrumented): State<Instrumented>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let timer = instrumented
+        .metrics
+        .request_latency
+        .with_label_values(&[instrumented.route])
+        .start_timer();
+    let res = next.run(req).await;
+    timer.observe_duration();
+    instrumented
+        .metrics
+        .requests
+        .with_label_values(&[instrumented.route, res.status().as_str()])
+        .inc();
+    res
+}
+
+/// Serves the metrics in the Prometheus text format.
+#[derive(Clone)]
+pub(crate) struct MetricsHandler {
+    metrics: Arc<Metrics>,
+    store: Arc<dyn Store>,
+    state: SharedState,
+}
+
+impl MetricsHandler {
+    pub(crate) fn new(metrics: Arc<Metrics>, store: Arc<dyn Store>, state: SharedState) -> MetricsHandler {
+        MetricsHandler {
+            metrics,
+            store,
+            state,
+        }
+    }
+
+    pub(crate) async fn handle(
+        State(handler): State<MetricsHandler>,
+        remote_addr: RemoteAddr,
+        headers: HeaderMap,
+    ) -> Response {
+        // The sensor values are only exported to clients that see them all
+        let state = handler.state.current();
+        let view = views::select(
+            &state.views,
+            remote_addr.0.map(|addr| addr.ip()),
+            bearer_token(&headers),
+        );
+        if view.is_some_and(View::hides_fields) {
+            return error_response(
+                StatusCode::FORBIDDEN,
+                "Metrics are only served to clients whose view hides nothing",
+            );
+        }
+
+        // Refresh the sensor value gauges
+        let timer = handler
+            .metrics
+            .store_latency
+            .with_label_values(&["mget"])
+            .start_timer();
+        match sensors::get_sensor_values(&state.sensor_specs, &*handler.store).await {
+            Ok(values) => handler.metrics.set_sensor_values(&state.sensor_specs, &values),
+            Err(e) => debug!("Could not refresh sensor value metrics: {}", e),
+        }
+        timer.observe_duration();
+
+        let content_type = TextEncoder::new().format_type().to_owned();
+        (
+            StatusCode::OK,
+            [
+                (
+                    header::CONTENT_TYPE,
+                    HeaderValue::from_str(&content_type).unwrap(),
+                ),
+                (header::CACHE_CONTROL, HeaderValue::from_static("no-cache")),
+            ],
+            handler.metrics.encode(),
+        )
+            .into_response()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::sensors::TemperatureSensorTemplate;
+
+    fn spec(data_key: &str) -> SensorSpec {
+        let template = TemperatureSensorTemplate {
+            metadata: Default::default(),
+            unit: "°C".into(),
+        };
+        SensorSpec::new(template, data_key.into())
+    }
+
+    #[test]
+    fn sensor_value_gauges() {
+        let metrics = Metrics::new();
+        let specs = [spec("temp_room1"), spec("temp_room2")];
+        metrics.set_sensor_values(&specs, &[Some("13.37".into()), Some("foo".into())]);
+        let output = metrics.encode();
+        assert!(output.contains("spaceapi_sensor_value{data_key=\"temp_room1\"} 13.37"));
+        assert!(!output.contains("temp_room2"));
+
+        metrics.set_sensor_values(&specs, &[None, Some("42".into())]);
+        let output = metrics.encode();
+        assert!(!output.contains("temp_room1"));
+        assert!(output.contains("spaceapi_sensor_value{data_key=\"temp_room2\"} 42"));
+    }
+
+    #[test]
+    fn reset_sensors() {
+        let metrics = Metrics::new();
+        metrics.set_sensor_values(&[spec("temp_room1")], &[Some("13.37".into())]);
+        metrics.sensor_updates.with_label_values(&["temp_room1"]).inc();
+        metrics.requests.with_label_values(&["root", "200"]).inc();
+        metrics.reset_sensors();
+        let output = metrics.encode();
+        assert!(!output.contains("temp_room1"));
+        assert!(output.contains("spaceapi_http_requests_total{code=\"200\",route=\"root\"} 1"));
+    }
+}
@@ -1,61 +1,134 @@
 //! The SpaceAPI server struct.
 
+use std::fmt;
+use std::future::IntoFuture;
 use std::net::ToSocketAddrs;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 
-use iron::Iron;
-use log::debug;
+use axum::extract::connect_info::Connected;
+use axum::routing::{get, post, put};
+use axum::serve::{IncomingStream, Listener};
+use axum::{middleware, Router};
+use log::{debug, warn};
 use redis::{ConnectionInfo, IntoConnectionInfo};
-use router::Router;
 
 use serde_json::map::Map;
 use serde_json::Value;
 
+mod cache;
+mod cors;
+mod env;
 mod handlers;
+mod listener;
+mod locale;
+mod metrics;
+mod reload;
+mod shutdown;
+mod status_file;
+#[cfg(unix)]
+mod systemd;
+#[cfg(feature = "tls")]
+mod tls;
+mod versions;
+
+pub use self::reload::ReloadHandle;
+pub use self::shutdown::ShutdownHandle;
 
 use crate::api;
 
 use crate::errors::SpaceapiServerError;
+use crate::events::EventLog;
 use crate::modifiers;
+use crate::presence::Presence;
 use crate::sensors;
-use crate::types::RedisPool;
+use crate::store::{RedisStore, Store};
+use crate::views;
 
-enum RedisInfo {
+enum StoreInfo {
     None,
-    Pool(r2d2::Pool<redis::Client>),
+    Store(Arc<dyn Store>),
     ConnectionInfo(ConnectionInfo),
     Err(SpaceapiServerError),
 }
 
+/// Add the library versions to the status extensions.
+fn add_version_extension(status: &mut api::Status) {
+    // Instantiate versions object
+    let mut versions = Map::new();
+    versions.insert("spaceapi-rs".into(), api::get_version().into());
+    versions.insert("spaceapi-server-rs".into(), crate::get_version().into());
+
+    // Add to extensions
+    status
+        .extensions
+        .insert("versions".into(), Value::Object(versions));
+}
+
 /// Builder to create a new [`SpaceapiServer`](struct.SpaceapiServer.html)
 /// instance.
 pub struct SpaceapiServerBuilder {
     status: api::Status,
-    redis_info: RedisInfo,
+    store_info: StoreInfo,
     sensor_specs: Vec<sensors::SensorSpec>,
-    status_modifiers: Vec<Box<dyn modifiers::StatusModifier>>,
+    status_modifiers: reload::StatusModifiers,
+    default_locale: String,
+    response_cache_ttl: Option<Duration>,
+    cors_policy: cors::CorsPolicy,
+    status_file: Option<(PathBuf, Duration)>,
+    views: Vec<views::View>,
+    presence: Option<Presence>,
+    event_log: Option<EventLog>,
+    #[cfg(feature = "validation")]
+    validation: crate::ValidationMode,
 }
 
 impl SpaceapiServerBuilder {
     /// Create a new builder instance based on the provided static status data.
     pub fn new(mut status: api::Status) -> SpaceapiServerBuilder {
-        // Instantiate versions object
-        let mut versions = Map::new();
-        versions.insert("spaceapi-rs".into(), api::get_version().into());
-        versions.insert("spaceapi-server-rs".into(), crate::get_version().into());
-
-        // Add to extensions
-        status
-            .extensions
-            .insert("versions".into(), Value::Object(versions));
+        add_version_extension(&mut status);
 
         SpaceapiServerBuilder {
             status,
-            redis_info: RedisInfo::None,
+            store_info: StoreInfo::None,
             sensor_specs: vec![],
             status_modifiers: vec![],
+            default_locale: "en".into(),
+            response_cache_ttl: None,
+            cors_policy: cors::CorsPolicy::default(),
+            status_file: None,
+            views: vec![],
+            presence: None,
+            event_log: None,
+            #[cfg(feature = "validation")]
+            validation: crate::ValidationMode::Off,
+        }
+    }
+
+    /// Create a new builder instance based on the static status data in a
+    /// SpaceAPI JSON file.
+    ///
+    /// The file is watched for changes, which are applied without restarting
+    /// the server. If a changed file cannot be parsed, an error is logged and
+    /// the previous status is kept.
+    pub fn from_status_file<P: AsRef<Path>>(path: P) -> Result<SpaceapiServerBuilder, SpaceapiServerError> {
+        let path = path.as_ref();
+        let mut builder = SpaceapiServerBuilder::new(status_file::load(path)?);
+        builder.status_file = Some((path.to_owned(), Duration::from_secs(2)));
+        Ok(builder)
+    }
+
+    /// Set how often the status file is checked for changes.
+    ///
+    /// Only applies to builders created with
+    /// [`from_status_file`](struct.SpaceapiServerBuilder.html#method.from_status_file).
+    /// Defaults to 2 seconds.
+    pub fn status_file_poll_interval(mut self, interval: Duration) -> Self {
+        if let Some((_, poll_interval)) = &mut self.status_file {
+            *poll_interval = interval;
         }
+        self
     }
 
     /// Specify a Redis connection string.
@@ -70,9 +143,9 @@ impl SpaceapiServerBuilder {
     /// ...
     /// ```
     pub fn redis_connection_info<R: IntoConnectionInfo>(mut self, redis_connection_info: R) -> Self {
-        self.redis_info = match redis_connection_info.into_connection_info() {
-            Ok(ci) => RedisInfo::ConnectionInfo(ci),
-            Err(e) => RedisInfo::Err(e.into()),
+        self.store_info = match redis_connection_info.into_connection_info() {
+            Ok(ci) => StoreInfo::ConnectionInfo(ci),
+            Err(e) => StoreInfo::Err(e.into()),
         };
         self
     }
@@ -85,8 +158,18 @@ impl SpaceapiServerBuilder {
     /// See
     /// [`examples/with_custom_redis_pool.rs`](https://github.com/spaceapi-community/spaceapi-server-rs/blob/master/examples/with_custom_redis_pool.rs)
     /// for a real example.
-    pub fn redis_pool(mut self, redis_pool: r2d2::Pool<redis::Client>) -> Self {
-        self.redis_info = RedisInfo::Pool(redis_pool);
+    ///
+    /// The pool is blocking, so all Redis commands are run on a thread
+    /// dedicated to blocking operations.
+    pub fn redis_pool(self, redis_pool: r2d2::Pool<redis::Client>) -> Self {
+        self.store(redis_pool)
+    }
+
+    /// Use a custom [`Store`](store/trait.Store.html) implementation for the
+    /// dynamic data, as an alternative to
+    /// [`redis_connection_info`](struct.SpaceapiServerBuilder.html#method.redis_connection_info).
+    pub fn store<S: Store + 'static>(mut self, store: S) -> Self {
+        self.store_info = StoreInfo::Store(Arc::new(store));
         self
     }
 
@@ -96,9 +179,82 @@ impl SpaceapiServerBuilder {
     /// This can be an instance of
     /// [`modifiers::StateFromPeopleNowPresent`](modifiers/struct.StateFromPeopleNowPresent.html),
     /// or your own implementation that uses the dynamic sensor data and/or
-    /// external data.
-    pub fn add_status_modifier<M: modifiers::StatusModifier + 'static>(mut self, modifier: M) -> Self {
-        self.status_modifiers.push(Box::new(modifier));
+    /// external data. Closures taking a `&mut api::Status` are status
+    /// modifiers as well, see also
+    /// [`add_status_modifier_fn`](#method.add_status_modifier_fn).
+    pub fn add_status_modifier<M: modifiers::StatusModifier + 'static>(self, modifier: M) -> Self {
+        self.add_contextual_status_modifier(modifier)
+    }
+
+    /// Add a closure as status modifier.
+    ///
+    /// This is a shortcut for
+    /// [`add_status_modifier`](#method.add_status_modifier) that doesn't
+    /// require annotating the argument type of the closure:
+    ///
+    /// ```rust,no_run
+    /// # use spaceapi_server::{api, SpaceapiServerBuilder};
+    /// # let status = api::Status::default();
+    /// let builder = SpaceapiServerBuilder::new(status).add_status_modifier_fn(|status| {
+    ///     status.state.get_or_insert_with(Default::default).message = Some("Hack the planet".into());
+    /// });
+    /// ```
+    pub fn add_status_modifier_fn<F>(self, modifier: F) -> Self
+    where
+        F: Fn(&mut api::Status) + Send + Sync + 'static,
+    {
+        self.add_status_modifier(modifier)
+    }
+
+    /// Add a status modifier with access to the store and the request, see
+    /// [`modifiers::ContextualStatusModifier`](modifiers/trait.ContextualStatusModifier.html).
+    ///
+    /// Status modifiers of both kinds are applied in the order they were
+    /// added. Errors returned by the modifier are logged and otherwise
+    /// ignored, see
+    /// [`add_contextual_status_modifier_with_policy`](#method.add_contextual_status_modifier_with_policy)
+    /// for the alternatives.
+    pub fn add_contextual_status_modifier<M: modifiers::ContextualStatusModifier + 'static>(
+        self,
+        modifier: M,
+    ) -> Self {
+        self.add_contextual_status_modifier_with_policy(modifier, modifiers::ErrorPolicy::default())
+    }
+
+    /// Add a status modifier with access to the store and the request, and
+    /// specify what happens if it returns an error.
+    ///
+    /// ```rust,no_run
+    /// # use async_trait::async_trait;
+    /// # use spaceapi_server::api;
+    /// # use spaceapi_server::modifiers::{Context, ContextualStatusModifier, ErrorPolicy, ModifierError};
+    /// # use spaceapi_server::SpaceapiServerBuilder;
+    /// # struct ClosedForEvent;
+    /// # #[async_trait]
+    /// # impl ContextualStatusModifier for ClosedForEvent {
+    /// #     async fn modify_with_context(&self, _: &mut api::Status, _: &Context<'_>) -> Result<(), ModifierError> {
+    /// #         Ok(())
+    /// #     }
+    /// # }
+    /// # let status = api::Status::default();
+    /// // Serve the status without the event state, but tell the clients
+    /// let builder = SpaceapiServerBuilder::new(status)
+    ///     .add_contextual_status_modifier_with_policy(ClosedForEvent, ErrorPolicy::Degrade);
+    /// ```
+    pub fn add_contextual_status_modifier_with_policy<M: modifiers::ContextualStatusModifier + 'static>(
+        mut self,
+        modifier: M,
+        policy: modifiers::ErrorPolicy,
+    ) -> Self {
+        self.status_modifiers.push((Box::new(modifier), policy));
+        self
+    }
+
+    /// Set the locale of the default messages, i.e. the ones the status
+    /// modifiers render without a translation. Clients that prefer it over
+    /// the translations get the default messages. Defaults to `en`.
+    pub fn default_locale<S: Into<String>>(mut self, locale: S) -> Self {
+        self.default_locale = locale.into();
         self
     }
 
@@ -111,50 +267,174 @@ impl SpaceapiServerBuilder {
         template: T,
         data_key: String,
     ) -> Self {
-        self.sensor_specs.push(sensors::SensorSpec {
-            template: Box::new(template),
-            data_key,
-        });
+        self.sensor_specs
+            .push(sensors::SensorSpec::new(template, data_key));
+        self
+    }
+
+    /// Cache the rendered status for the specified duration.
+    ///
+    /// By default, every request reads all sensors and runs all status
+    /// modifiers. With a response cache, a burst of requests only costs a
+    /// single render. The cache is invalidated whenever a sensor value is
+    /// updated through the HTTP API. Values written to Redis directly become
+    /// visible once the cached response expires.
+    ///
+    /// The serialized status is cached per path, view and locale. It isn't
+    /// cached if a status modifier isn't
+    /// [cacheable](modifiers/trait.ContextualStatusModifier.html#method.cacheable).
+    /// Custom status modifiers, including closures, aren't cacheable unless
+    /// they opt in with
+    /// [`StatusModifier::cacheable`](modifiers/trait.StatusModifier.html#method.cacheable).
+    pub fn response_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.response_cache_ttl = Some(ttl);
+        self
+    }
+
+    /// Check the served status against the bundled SpaceAPI schemas.
+    ///
+    /// The status is validated after all sensors and status modifiers have
+    /// been applied, so that modifiers producing an invalid status are
+    /// noticed. Validation is off by default.
+    ///
+    /// This method is only available with the `validation` feature.
+    #[cfg(feature = "validation")]
+    pub fn validation(mut self, mode: crate::ValidationMode) -> Self {
+        self.validation = mode;
+        self
+    }
+
+    /// Validate the static status against the bundled SpaceAPI schemas of
+    /// all versions it declares, e.g. once at startup.
+    ///
+    /// Sensors and status modifiers are not applied. To check the status as
+    /// it is served, use [`validation`](#method.validation).
+    ///
+    /// This method is only available with the `validation` feature.
+    #[cfg(feature = "validation")]
+    pub fn validate(&self) -> Result<(), SpaceapiServerError> {
+        crate::validation::validate(&self.status)
+    }
+
+    /// Add a view of the status, e.g. to hide sensor details from the
+    /// public. Views are tried in the order they were added, see the
+    /// [`views`](views/index.html) module. The last view must apply to all
+    /// clients.
+    ///
+    /// With views, the `/metrics` endpoint is only served to clients whose
+    /// view hides nothing, as it exports the sensor values.
+    pub fn add_view(mut self, view: views::View) -> Self {
+        self.views.push(view);
+        self
+    }
+
+    /// Let members check in and out at `/presence/<name>/`, listing the
+    /// names of those who agree in the `people_now_present` sensor. See the
+    /// [`presence`](presence/index.html) module.
+    pub fn presence(mut self, presence: Presence) -> Self {
+        self.presence = Some(presence);
+        self
+    }
+
+    /// Record sensor updates, state changes and check-ins in an event log,
+    /// and accept other events at `POST /events`. Serve them with the
+    /// [`RecentEvents`](modifiers/struct.RecentEvents.html) modifier, see
+    /// the [`events`](events/index.html) module.
+    pub fn event_log(mut self, event_log: EventLog) -> Self {
+        self.event_log = Some(event_log);
+        self
+    }
+
+    /// Restrict the origins allowed to access the API from a browser.
+    ///
+    /// By default, any origin is allowed (`Access-Control-Allow-Origin: *`).
+    /// Pass a list of origins like `https://dashboard.example.com` to only
+    /// allow those. A `*` entry allows any origin. An empty list disables
+    /// CORS, i.e. no CORS headers are sent.
+    pub fn cors_allowed_origins<I, S>(mut self, origins: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.cors_policy.allowed_origins = origins.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set the methods announced in CORS preflight responses.
+    ///
+    /// Defaults to `GET`, `POST`, `PUT`, `DELETE` and `OPTIONS`.
+    pub fn cors_allowed_methods<I, S>(mut self, methods: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.cors_policy.allowed_methods = methods.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Set the request headers announced in CORS preflight responses.
+    ///
+    /// Defaults to `Authorization` and `Content-Type`.
+    pub fn cors_allowed_headers<I, S>(mut self, headers: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.cors_policy.allowed_headers = headers.into_iter().map(Into::into).collect();
         self
     }
 
     /// Build a server instance.
     ///
-    /// This can fail if not all required data has been provided.
-    pub fn build(self) -> Result<SpaceapiServer, SpaceapiServerError> {
-        let pool = match self.redis_info {
-            RedisInfo::None => Err("No redis connection defined".into()),
-            RedisInfo::Err(e) => Err(e),
-            RedisInfo::Pool(p) => Ok(p),
-            RedisInfo::ConnectionInfo(ci) => {
+    /// If the `REDIS_URL` environment variable is set, it overrides the
+    /// connection info set with
+    /// [`redis_connection_info`](struct.SpaceapiServerBuilder.html#method.redis_connection_info).
+    /// A custom Redis pool or store is always used as-is.
+    ///
+    /// This can fail if not all required data has been provided, or if the
+    /// last view doesn't apply to all clients.
+    pub fn build(mut self) -> Result<SpaceapiServer, SpaceapiServerError> {
+        if let Some(redis_url) = env::var(env::REDIS_URL) {
+            if let StoreInfo::Store(_) = self.store_info {
+                debug!("Using custom store, ignoring {}", env::REDIS_URL);
+            } else {
+                self = self.redis_connection_info(redis_url);
+            }
+        }
+
+        let state = reload::SharedState::new(reload::ServerState::from_builder(&mut self)?);
+
+        let store: Result<Arc<dyn Store>, SpaceapiServerError> = match self.store_info {
+            StoreInfo::None => Err("No redis connection defined".into()),
+            StoreInfo::Err(e) => Err(e),
+            StoreInfo::Store(store) => Ok(store),
+            StoreInfo::ConnectionInfo(ci) => {
                 // Log some useful debug information
                 debug!("Connecting to redis database {} at {:?}", ci.redis.db, ci.addr);
 
-                let client: redis::Client = redis::Client::open(ci)?;
-
-                let redis_pool: r2d2::Pool<redis::Client> = r2d2::Pool::builder()
-                    // Provide up to 6 connections in connection pool
-                    .max_size(6)
-                    // At least 1 connection must be active
-                    .min_idle(Some(2))
-                    // Try to get a connection for max 1 second
-                    .connection_timeout(Duration::from_secs(1))
-                    // Don't log errors directly.
-                    // They can get quite verbose, and we're already catching and
-                    // logging the corresponding results anyways.
-                    .error_handler(Box::new(r2d2::NopErrorHandler))
-                    // Initialize connection pool lazily. This allows the SpaceAPI
-                    // server to work even without a database connection.
-                    .build_unchecked(client);
-                Ok(redis_pool)
+                // The connection is established lazily. This allows the
+                // SpaceAPI server to work even without a database connection.
+                Ok(Arc::new(RedisStore::new(ci)?))
             }
         };
 
+        let response_cache = self
+            .response_cache_ttl
+            .map(|ttl| Arc::new(cache::ResponseCache::new(ttl)));
+
+        if let Some((path, interval)) = self.status_file {
+            status_file::watch(path, interval, state.downgrade(), response_cache.clone());
+        }
+
         Ok(SpaceapiServer {
-            status: self.status,
-            redis_pool: pool?,
-            sensor_specs: Arc::new(self.sensor_specs),
-            status_modifiers: self.status_modifiers,
+            state,
+            store: store?,
+            response_cache,
+            metrics: Arc::new(metrics::Metrics::new()),
+            cors_policy: self.cors_policy,
+            #[cfg(feature = "validation")]
+            validation: self.validation,
+            shutdown_handle: ShutdownHandle::default(),
         })
     }
 }
@@ -165,51 +445,323 @@ impl SpaceapiServerBuilder {
 /// passing it the host, the port, the ``Status`` object and a redis connection info object.
 ///
 /// The ``SpaceapiServer`` includes a web server through
-/// [Hyper](http://hyper.rs/hyper/hyper/server/index.html). Simply call the ``serve`` method.
+/// [axum](https://docs.rs/axum/). Simply call the async ``serve`` method.
 pub struct SpaceapiServer {
-    status: api::Status,
-    redis_pool: RedisPool,
-    sensor_specs: sensors::SafeSensorSpecs,
-    status_modifiers: Vec<Box<dyn modifiers::StatusModifier>>,
+    state: reload::SharedState,
+    store: Arc<dyn Store>,
+    response_cache: Option<Arc<cache::ResponseCache>>,
+    metrics: Arc<metrics::Metrics>,
+    cors_policy: cors::CorsPolicy,
+    shutdown_handle: ShutdownHandle,
+    #[cfg(feature = "validation")]
+    validation: crate::ValidationMode,
 }
 
 impl SpaceapiServer {
-    /// Create and return a Router instance.
-    fn route(self) -> Router {
-        let mut router = Router::new();
+    /// Return a handle to reload the static status, the sensors, the status
+    /// modifiers, the views, the presence list and the event log while the
+    /// server is running.
+    pub fn reload_handle(&self) -> ReloadHandle {
+        ReloadHandle {
+            state: self.state.clone(),
+            response_cache: self.response_cache.clone(),
+            metrics: self.metrics.clone(),
+        }
+    }
+
+    /// Return a handle to gracefully shut down the server once it is
+    /// serving.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        self.shutdown_handle.clone()
+    }
+
+    /// Return the router with all SpaceAPI routes, to embed them into an
+    /// existing axum application instead of calling ``serve``.
+    ///
+    /// The router can be merged into a router with any state. Note that a
+    /// shutdown handle has no effect on an embedded router, the application
+    /// is responsible for shutting down its server.
+    ///
+    /// ```no_run
+    /// # use spaceapi_server::SpaceapiServer;
+    /// # fn portal_routes(server: SpaceapiServer) -> axum::Router {
+    /// axum::Router::new()
+    ///     .route("/members", axum::routing::get(|| async { "Members" }))
+    ///     .merge(server.into_router())
+    /// # }
+    /// ```
+    pub fn into_router<S>(self) -> Router<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        let metrics = self.metrics;
+        let instrumented = |route| {
+            middleware::from_fn_with_state(
+                metrics::Instrumented::new(route, metrics.clone()),
+                metrics::count_requests,
+            )
+        };
+
+        let read_handler = handlers::ReadHandler::new(
+            self.state.clone(),
+            self.store.clone(),
+            self.response_cache.clone(),
+            metrics.clone(),
+        );
+        #[cfg(feature = "validation")]
+        let read_handler = read_handler.with_validation(self.validation);
+        let update_handler = handlers::UpdateHandler::new(
+            self.state.clone(),
+            self.store.clone(),
+            self.response_cache.clone(),
+            metrics.clone(),
+        );
+        let readiness_handler = handlers::ReadinessHandler::new(self.store.clone(), metrics.clone());
+        let metrics_handler =
+            metrics::MetricsHandler::new(metrics.clone(), self.store.clone(), self.state.clone());
 
-        router.get(
+        let mut router = Router::new().route(
             "/",
-            handlers::ReadHandler::new(
-                self.status.clone(),
-                self.redis_pool.clone(),
-                self.sensor_specs.clone(),
-                self.status_modifiers,
-            ),
-            "root",
+            get(handlers::ReadHandler::handle)
+                .with_state(read_handler.clone())
+                .route_layer(instrumented("root"))
+                // Answer CORS preflight requests
+                .options(cors::preflight),
         );
+        for version in versions::SchemaVersion::ALL {
+            router = router.route(
+                version.route(),
+                get(handlers::ReadHandler::handle)
+                    .with_state(read_handler.clone().with_version(version))
+                    .route_layer(instrumented(version.name()))
+                    .options(cors::preflight),
+            );
+        }
 
-        router.put(
-            "/sensors/:sensor/",
-            handlers::UpdateHandler::new(self.redis_pool.clone(), self.sensor_specs),
-            "sensors",
+        // The presence list and the event log may be enabled by a reload,
+        // so their routes always exist
+        let presence_handler = handlers::PresenceHandler::new(
+            self.state.clone(),
+            self.store.clone(),
+            self.response_cache.clone(),
+            metrics.clone(),
+        );
+        let events_handler = handlers::EventsHandler::new(
+            self.state.clone(),
+            self.store.clone(),
+            self.response_cache.clone(),
+            metrics.clone(),
         );
+        router = router
+            .route(
+                "/presence/{name}/",
+                put(handlers::PresenceHandler::check_in)
+                    .delete(handlers::PresenceHandler::check_out)
+                    .with_state(presence_handler)
+                    .route_layer(instrumented("presence"))
+                    .options(cors::preflight),
+            )
+            .route(
+                "/events",
+                post(handlers::EventsHandler::handle)
+                    .with_state(events_handler)
+                    .route_layer(instrumented("events"))
+                    .options(cors::preflight),
+            );
 
         router
+            .route(
+                "/sensors/{sensor}/",
+                put(handlers::UpdateHandler::handle)
+                    .with_state(update_handler)
+                    .route_layer(instrumented("sensors"))
+                    .options(cors::preflight),
+            )
+            .route(
+                "/healthz",
+                get(handlers::health)
+                    .route_layer(instrumented("healthz"))
+                    .options(cors::preflight),
+            )
+            .route(
+                "/readyz",
+                get(handlers::ReadinessHandler::handle)
+                    .with_state(readiness_handler)
+                    .route_layer(instrumented("readyz"))
+                    .options(cors::preflight),
+            )
+            .route(
+                "/metrics",
+                get(metrics::MetricsHandler::handle)
+                    .with_state(metrics_handler)
+                    .route_layer(instrumented("metrics"))
+                    .options(cors::preflight),
+            )
+            .layer(middleware::from_fn_with_state(self.cors_policy, cors::cors))
     }
 
-    /// Start a HTTP server listening on ``self.host:self.port``.
+    /// Return the router with all SpaceAPI routes below the specified path
+    /// prefix, e.g. `/spaceapi`.
     ///
-    /// The call returns an `HttpResult<Listening>` object, see
-    /// http://ironframework.io/doc/hyper/server/struct.Listening.html
-    /// for more information.
-    pub fn serve<S: ToSocketAddrs>(self, socket_addr: S) -> crate::HttpResult<crate::Listening> {
-        // Launch server process
-        let router = self.route();
+    /// The status is then served at the prefix itself (`/spaceapi`, without
+    /// a trailing slash) and sensors are updated at
+    /// `/spaceapi/sensors/<sensor-id>/`. An empty prefix or `/` is the same
+    /// as [`into_router`](#method.into_router).
+    pub fn into_router_with_prefix<S>(self, prefix: &str) -> Router<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        let prefix = prefix.trim_end_matches('/');
+        if prefix.is_empty() {
+            return self.into_router();
+        }
+        let prefix = if prefix.starts_with('/') {
+            prefix.to_owned()
+        } else {
+            format!("/{}", prefix)
+        };
+        Router::new().nest(&prefix, self.into_router())
+    }
+
+    /// Start a HTTP server listening on the specified address.
+    ///
+    /// The host and port can be overridden with the `HOST` and `PORT`
+    /// environment variables.
+    ///
+    /// The returned future runs until the server is stopped through the
+    /// handle returned by [`shutdown_handle`](#method.shutdown_handle).
+    pub async fn serve<S: ToSocketAddrs>(self, socket_addr: S) -> Result<(), SpaceapiServerError> {
+        let addrs = env::listen_addrs(socket_addr)?;
+        let listener = tokio::net::TcpListener::bind(&*addrs).await?;
+        println!("Starting HTTP server on:");
+        println!("\thttp://{}", listener.local_addr()?);
+        self.serve_listener(listener).await
+    }
+
+    /// Start a HTTP server listening on the Unix domain socket at the
+    /// specified path, e.g. to serve requests from a reverse proxy without
+    /// opening a TCP port.
+    ///
+    /// A stale socket file left behind by a previous run is replaced, and the
+    /// socket file is removed again when the server stops. The permissions of
+    /// the socket file follow the umask of the process.
+    ///
+    /// This method is only available on Unix.
+    #[cfg(unix)]
+    pub async fn serve_unix<P: AsRef<Path>>(self, path: P) -> Result<(), SpaceapiServerError> {
+        use std::os::unix::fs::FileTypeExt;
+
+        let path = path.as_ref();
+        if std::fs::symlink_metadata(path).is_ok_and(|meta| meta.file_type().is_socket()) {
+            debug!("Removing stale socket {}", path.display());
+            std::fs::remove_file(path)?;
+        }
+        let listener = tokio::net::UnixListener::bind(path)?;
         println!("Starting HTTP server on:");
-        for a in socket_addr.to_socket_addrs()? {
-            println!("\thttp://{}", a);
+        println!("\tunix:{}", path.display());
+        let result = self.serve_listener(listener).await;
+        if let Err(e) = std::fs::remove_file(path) {
+            warn!("Could not remove socket {}: {}", path.display(), e);
         }
-        Iron::new(router).http(socket_addr)
+        result
+    }
+
+    /// Start a HTTP server on the socket passed by systemd through socket
+    /// activation (the `LISTEN_FDS` environment variable).
+    ///
+    /// Both TCP and Unix domain sockets are supported. If more than one
+    /// socket is passed, only the first one is used.
+    ///
+    /// This method is only available on Unix.
+    #[cfg(unix)]
+    pub async fn serve_systemd(self) -> Result<(), SpaceapiServerError> {
+        match systemd::take_listener()? {
+            systemd::ActivatedListener::Tcp(listener) => {
+                let listener = tokio::net::TcpListener::from_std(listener)?;
+                println!("Starting HTTP server on:");
+                println!("\thttp://{} (systemd)", listener.local_addr()?);
+                self.serve_listener(listener).await
+            }
+            systemd::ActivatedListener::Unix(listener) => {
+                let listener = tokio::net::UnixListener::from_std(listener)?;
+                println!("Starting HTTP server on:");
+                match listener.local_addr()?.as_pathname() {
+                    Some(path) => println!("\tunix:{} (systemd)", path.display()),
+                    None => println!("\tunix socket (systemd)"),
+                }
+                self.serve_listener(listener).await
+            }
+        }
+    }
+
+    /// Start a HTTPS server listening on the specified address.
+    ///
+    /// The certificate chain and the private key are read from the specified
+    /// PEM files. The files are checked for changes every minute, so renewed
+    /// certificates (e.g. from Let's Encrypt) are picked up without a
+    /// restart. If the new files are invalid, an error is logged and the
+    /// previous certificate is kept.
+    ///
+    /// Like with [`serve`](#method.serve), the host and port can be
+    /// overridden with the `HOST` and `PORT` environment variables.
+    ///
+    /// This method is only available with the `tls` feature.
+    #[cfg(feature = "tls")]
+    pub async fn serve_tls<S, C, K>(
+        self,
+        socket_addr: S,
+        cert_path: C,
+        key_path: K,
+    ) -> Result<(), SpaceapiServerError>
+    where
+        S: ToSocketAddrs,
+        C: AsRef<Path>,
+        K: AsRef<Path>,
+    {
+        let addrs = env::listen_addrs(socket_addr)?;
+        let listener = tokio::net::TcpListener::bind(&*addrs).await?;
+        let listener = tls::TlsListener::new(listener, cert_path.as_ref(), key_path.as_ref())?;
+        println!("Starting HTTPS server on:");
+        println!("\thttps://{}", listener.local_addr()?);
+        self.serve_listener(listener).await
+    }
+
+    /// Serve requests from the specified listener until the server is shut
+    /// down.
+    async fn serve_listener<L>(self, listener: L) -> Result<(), SpaceapiServerError>
+    where
+        L: Listener,
+        L::Addr: fmt::Debug,
+        listener::RemoteAddr: for<'a> Connected<IncomingStream<'a, L>>,
+    {
+        let shutdown_handle = self.shutdown_handle.clone();
+        shutdown_handle.start();
+        let app: Router = self.into_router();
+        let server = axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<listener::RemoteAddr>(),
+        )
+        .with_graceful_shutdown({
+            let shutdown_handle = shutdown_handle.clone();
+            async move {
+                shutdown_handle.requested().await;
+            }
+        });
+
+        let graceful = tokio::select! {
+            result = server.into_future() => {
+                result?;
+                true
+            }
+            _ = async {
+                let timeout = shutdown_handle.requested().await;
+                tokio::time::sleep(timeout).await;
+            } => {
+                warn!("Timed out waiting for in-flight requests");
+                false
+            }
+        };
+        shutdown_handle.finish(graceful);
+        Ok(())
     }
 }
@@ -0,0 +1,146 @@
+//! Swapping the static status, sensors, status modifiers and access settings
+//! at runtime.
+
+use std::sync::{Arc, RwLock, Weak};
+
+use super::cache::ResponseCache;
+use super::metrics::Metrics;
+use super::SpaceapiServerBuilder;
+use crate::api;
+use crate::errors::SpaceapiServerError;
+use crate::events::EventLog;
+use crate::modifiers;
+use crate::presence::Presence;
+use crate::sensors;
+use crate::views::{self, View};
+
+/// The registered status modifiers with their error policies.
+pub(crate) type StatusModifiers = Vec<(
+    Box<dyn modifiers::ContextualStatusModifier>,
+    modifiers::ErrorPolicy,
+)>;
+
+/// The parts of the server configuration that can be reloaded at runtime.
+pub(crate) struct ServerState {
+    pub(crate) status: api::Status,
+    pub(crate) sensor_specs: sensors::SafeSensorSpecs,
+    pub(crate) status_modifiers: Arc<StatusModifiers>,
+    pub(crate) default_locale: Arc<str>,
+    pub(crate) views: Arc<Vec<View>>,
+    pub(crate) presence: Option<Arc<Presence>>,
+    pub(crate) event_log: Option<Arc<EventLog>>,
+}
+
+impl ServerState {
+    /// Take the reloadable parts of the builder configuration. Fails if the
+    /// views don't cover all clients.
+    pub(crate) fn from_builder(
+        builder: &mut SpaceapiServerBuilder,
+    ) -> Result<ServerState, SpaceapiServerError> {
+        views::check(&builder.views)?;
+        Ok(ServerState {
+            status: std::mem::take(&mut builder.status),
+            sensor_specs: Arc::new(std::mem::take(&mut builder.sensor_specs)),
+            status_modifiers: Arc::new(std::mem::take(&mut builder.status_modifiers)),
+            default_locale: std::mem::take(&mut builder.default_locale).into(),
+            views: Arc::new(std::mem::take(&mut builder.views)),
+            presence: builder.presence.take().map(Arc::new),
+            event_log: builder.event_log.take().map(Arc::new),
+        })
+    }
+
+    /// Return the locales the status modifiers provide messages in.
+    pub(crate) fn locales(&self) -> Vec<String> {
+        let mut locales: Vec<String> = self
+            .status_modifiers
+            .iter()
+            .flat_map(|(modifier, _)| modifier.available_locales())
+            .collect();
+        locales.sort();
+        locales.dedup();
+        locales
+    }
+
+    /// Return the `state.open` of the static status.
+    pub(crate) fn static_open(&self) -> Option<bool> {
+        self.status.state.as_ref().and_then(|state| state.open)
+    }
+}
+
+/// An atomically swappable handle to the current ``ServerState``.
+///
+/// Readers get a consistent snapshot, even if the state is replaced while a
+/// request is being processed.
+#[derive(Clone)]
+pub(crate) struct SharedState(Arc<RwLock<Arc<ServerState>>>);
+
+impl SharedState {
+    pub(crate) fn new(state: ServerState) -> SharedState {
+        SharedState(Arc::new(RwLock::new(Arc::new(state))))
+    }
+
+    /// Return the current state.
+    pub(crate) fn current(&self) -> Arc<ServerState> {
+        self.0.read().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Replace the current state.
+    pub(crate) fn replace(&self, state: ServerState) {
+        *self.0.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(state);
+    }
+
+    /// Replace the static status, keeping sensors and status modifiers.
+    pub(crate) fn replace_status(&self, status: api::Status) {
+        let mut current = self.0.write().unwrap_or_else(|e| e.into_inner());
+        *current = Arc::new(ServerState {
+            status,
+            sensor_specs: current.sensor_specs.clone(),
+            status_modifiers: current.status_modifiers.clone(),
+            default_locale: current.default_locale.clone(),
+            views: current.views.clone(),
+            presence: current.presence.clone(),
+            event_log: current.event_log.clone(),
+        });
+    }
+
+    pub(crate) fn downgrade(&self) -> Weak<RwLock<Arc<ServerState>>> {
+        Arc::downgrade(&self.0)
+    }
+
+    pub(crate) fn upgrade(weak: &Weak<RwLock<Arc<ServerState>>>) -> Option<SharedState> {
+        weak.upgrade().map(SharedState)
+    }
+}
+
+/// A handle to reload the static status, the sensors, the status modifiers,
+/// the views, the presence list and the event log of a server without
+/// restarting it.
+///
+/// Use [`SpaceapiServer::reload_handle`](struct.SpaceapiServer.html#method.reload_handle)
+/// to get a handle. It can be cloned and sent to other threads, e.g. to a
+/// signal handler.
+#[derive(Clone)]
+pub struct ReloadHandle {
+    pub(crate) state: SharedState,
+    pub(crate) response_cache: Option<Arc<ResponseCache>>,
+    pub(crate) metrics: Arc<Metrics>,
+}
+
+impl ReloadHandle {
+    /// Replace the static status, the sensors, the status modifiers, the
+    /// views, the presence list and the event log with the ones of the
+    /// specified builder, e.g. to rotate tokens. Fails like
+    /// [`SpaceapiServerBuilder::build`] if the views are invalid, keeping
+    /// the previous settings.
+    ///
+    /// All other builder settings, like the Redis connection, the CORS policy
+    /// or the response cache, are ignored. Changing those requires a restart.
+    pub fn reload(&self, mut builder: SpaceapiServerBuilder) -> Result<(), SpaceapiServerError> {
+        self.state.replace(ServerState::from_builder(&mut builder)?);
+        if let Some(cache) = &self.response_cache {
+            cache.invalidate();
+        }
+        self.metrics.reset_sensors();
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+//! Graceful shutdown support.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::sync::watch;
+
+/// State shared between the server and all ``ShutdownHandle`` instances.
+struct Shared {
+    /// The grace period for in-flight requests, set once a shutdown has
+    /// been requested
+    requested: watch::Sender<Option<Duration>>,
+    /// Set once the server has stopped, to whether all in-flight requests
+    /// finished within the grace period
+    finished: watch::Sender<Option<bool>>,
+    serving: AtomicBool,
+}
+
+/// A handle to gracefully shut down a running server.
+///
+/// The handle can be obtained with
+/// [`SpaceapiServer::shutdown_handle`](struct.SpaceapiServer.html#method.shutdown_handle)
+/// before calling ``serve``. It can be cloned and sent to other tasks.
+#[derive(Clone)]
+pub struct ShutdownHandle {
+    shared: Arc<Shared>,
+}
+
+impl Default for ShutdownHandle {
+    fn default() -> Self {
+        ShutdownHandle {
+            shared: Arc::new(Shared {
+                requested: watch::Sender::new(None),
+                finished: watch::Sender::new(None),
+                serving: AtomicBool::new(false),
+            }),
+        }
+    }
+}
+
+impl ShutdownHandle {
+    /// Stop accepting new connections and wait for in-flight requests to
+    /// finish, for at most the specified timeout. Idle keep-alive
+    /// connections are closed right away. Once this returns, ``serve``
+    /// returns as well.
+    ///
+    /// Sensor updates are written to the store before their response is
+    /// sent, so there are no pending writes left once all requests are
+    /// finished.
+    ///
+    /// Returns `true` if all in-flight requests finished within the timeout.
+    pub async fn shutdown(&self, timeout: Duration) -> bool {
+        self.shared.requested.send_replace(Some(timeout));
+        if !self.shared.serving.load(Ordering::SeqCst) {
+            return true;
+        }
+        let mut finished = self.shared.finished.subscribe();
+        let graceful = finished.wait_for(Option::is_some).await.map(|graceful| *graceful);
+        graceful.ok().flatten().unwrap_or(true)
+    }
+
+    /// Return whether ``shutdown`` has been called.
+    pub fn is_shutting_down(&self) -> bool {
+        self.shared.requested.borrow().is_some()
+    }
+
+    /// Mark the server as serving, so that ``shutdown`` waits for it.
+    pub(crate) fn start(&self) {
+        self.shared.serving.store(true, Ordering::SeqCst);
+    }
+
+    /// Wait until a shutdown is requested and return the grace period.
+    pub(crate) async fn requested(&self) -> Duration {
+        let mut requested = self.shared.requested.subscribe();
+        // The sender lives as long as the handle, so this can't fail
+        let timeout = requested.wait_for(Option::is_some).await.map(|timeout| *timeout);
+        timeout.ok().flatten().unwrap_or_default()
+    }
+
+    /// Report that the server has stopped.
+    pub(crate) fn finish(&self, graceful: bool) {
+        self.shared.finished.send_replace(Some(graceful));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn shutdown_without_serving() {
+        let handle = ShutdownHandle::default();
+        assert!(!handle.is_shutting_down());
+        assert!(handle.shutdown(Duration::from_millis(10)).await);
+        assert!(handle.is_shutting_down());
+        assert_eq!(handle.requested().await, Duration::from_millis(10));
+    }
+
+    #[tokio::test]
+    async fn shutdown_waits_for_server() {
+        let handle = ShutdownHandle::default();
+        handle.start();
+        let server = {
+            let handle = handle.clone();
+            tokio::spawn(async move {
+                handle.requested().await;
+                handle.finish(false);
+            })
+        };
+        assert!(!handle.shutdown(Duration::from_millis(10)).await);
+        server.await.unwrap();
+    }
+}
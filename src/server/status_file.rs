@@ -0,0 +1,146 @@
+//! Loading and watching the static status from a SpaceAPI JSON file.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, Weak};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use log::{error, info};
+
+use super::cache::ResponseCache;
+use super::reload::{ServerState, SharedState};
+use crate::api;
+use crate::errors::SpaceapiServerError;
+
+/// Read and parse a SpaceAPI JSON file.
+pub(crate) fn load(path: &Path) -> Result<api::Status, SpaceapiServerError> {
+    let json = fs::read_to_string(path)
+        .map_err(|e| format!("Could not read status file {}: {}", path.display(), e))?;
+    serde_json::from_str(&json).map_err(|e| format!("Invalid status file {}: {}", path.display(), e).into())
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Poll the status file for changes and swap the status on change.
+///
+/// Invalid files are rejected and the previous status is kept. The watcher
+/// thread stops as soon as the server owning the status has been dropped.
+pub(crate) fn watch(
+    path: PathBuf,
+    interval: Duration,
+    state: Weak<RwLock<Arc<ServerState>>>,
+    response_cache: Option<Arc<ResponseCache>>,
+) {
+    let mut last_modified = modified(&path);
+    thread::spawn(move || loop {
+        thread::sleep(interval);
+        let state = match SharedState::upgrade(&state) {
+            Some(state) => state,
+            None => break,
+        };
+        let modified = modified(&path);
+        if modified == last_modified {
+            continue;
+        }
+        last_modified = modified;
+        match load(&path) {
+            Ok(mut new_status) => {
+                super::add_version_extension(&mut new_status);
+                state.replace_status(new_status);
+                if let Some(cache) = &response_cache {
+                    cache.invalidate();
+                }
+                info!("Reloaded status file {}", path.display());
+            }
+            Err(e) => error!("{}, keeping the previous status", e),
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+
+    use axum::body::Bytes;
+
+    const STATUS: &str = r#"{
+        "api_compatibility": ["14"],
+        "space": "ourspace",
+        "logo": "https://example.com/logo.png",
+        "url": "https://example.com/",
+        "location": {"lat": 47.123, "lon": 8.88},
+        "contact": {"email": "hi@example.com"}
+    }"#;
+
+    fn temp_file(name: &str, contents: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("spaceapi-server-{}-{}.json", name, std::process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn load_status() {
+        let path = temp_file("load", STATUS);
+        let status = load(&path).unwrap();
+        assert_eq!(status.space, "ourspace");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn load_invalid_status() {
+        let path = temp_file("invalid", "{}");
+        let err = load(&path).unwrap_err();
+        assert!(err.to_string().starts_with("Invalid status file"));
+        fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn watch_status() {
+        let path = temp_file("watch", STATUS);
+        let state = SharedState::new(ServerState {
+            status: load(&path).unwrap(),
+            sensor_specs: Arc::new(vec![]),
+            status_modifiers: Arc::new(vec![]),
+            default_locale: "en".into(),
+            views: Arc::new(vec![]),
+            presence: None,
+            event_log: None,
+        });
+        let cache = Arc::new(ResponseCache::new(Duration::from_secs(60)));
+        let rendered = |body: &'static str| async move { Ok::<_, ()>(Bytes::from(body)) };
+        cache.get_or_render("", || rendered("cached")).await.unwrap();
+        watch(
+            path.clone(),
+            Duration::from_millis(10),
+            state.downgrade(),
+            Some(cache.clone()),
+        );
+
+        // Invalid files are ignored
+        thread::sleep(Duration::from_millis(50));
+        fs::write(&path, "{").unwrap();
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(state.current().status.space, "ourspace");
+        assert_eq!(
+            cache.get_or_render("", || rendered("rendered")).await.unwrap(),
+            "cached"
+        );
+
+        // Valid files replace the status
+        fs::write(&path, STATUS.replace("ourspace", "otherspace")).unwrap();
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(state.current().status.space, "otherspace");
+        assert!(state.current().status.extensions.contains_key("versions"));
+        assert_eq!(
+            cache.get_or_render("", || rendered("rendered")).await.unwrap(),
+            "rendered"
+        );
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,108 @@
+//! Socket activation through systemd.
+//!
+//! systemd passes pre-opened sockets starting at file descriptor 3 and
+//! announces them with the `LISTEN_PID` and `LISTEN_FDS` environment
+//! variables, see `sd_listen_fds(3)`.
+
+use std::net::TcpListener;
+use std::os::fd::{FromRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixListener;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use log::warn;
+
+use super::env;
+use crate::errors::SpaceapiServerError;
+
+const LISTEN_PID: &str = "LISTEN_PID";
+const LISTEN_FDS: &str = "LISTEN_FDS";
+
+/// The first file descriptor passed by systemd.
+const LISTEN_FDS_START: RawFd = 3;
+
+/// Whether the passed socket has already been taken. Taking it twice would
+/// close the file descriptor twice.
+static TAKEN: AtomicBool = AtomicBool::new(false);
+
+/// A listening socket passed by systemd.
+pub(crate) enum ActivatedListener {
+    Tcp(TcpListener),
+    Unix(UnixListener),
+}
+
+/// Return the number of sockets passed to this process.
+fn listen_fds(pid: Option<&str>, fds: Option<&str>, own_pid: u32) -> Result<usize, SpaceapiServerError> {
+    let fds = fds.ok_or_else(|| format!("No sockets passed by systemd ({} is not set)", LISTEN_FDS))?;
+    if let Some(pid) = pid {
+        if pid.parse::<u32>().ok() != Some(own_pid) {
+            return Err(format!("The sockets passed by systemd are meant for process {}", pid).into());
+        }
+    }
+    match fds.parse::<usize>() {
+        Ok(0) => Err("No sockets passed by systemd".into()),
+        Ok(fds) => Ok(fds),
+        Err(_) => Err(format!("Invalid {} environment variable: {}", LISTEN_FDS, fds).into()),
+    }
+}
+
+/// Take the first socket passed by systemd.
+///
+/// If more than one socket is passed, only the first one is used.
+pub(crate) fn take_listener() -> Result<ActivatedListener, SpaceapiServerError> {
+    let fds = listen_fds(
+        env::var(LISTEN_PID).as_deref(),
+        env::var(LISTEN_FDS).as_deref(),
+        process::id(),
+    )?;
+    if fds > 1 {
+        warn!("systemd passed {} sockets, only the first one is used", fds);
+    }
+    if TAKEN.swap(true, Ordering::SeqCst) {
+        return Err("The socket passed by systemd is already in use".into());
+    }
+
+    // SAFETY: systemd guarantees that the announced file descriptors are
+    // open, and `TAKEN` makes sure that only one `OwnedFd` is created.
+    let fd = unsafe { OwnedFd::from_raw_fd(LISTEN_FDS_START) };
+
+    // Querying the local address fails if the address family doesn't match
+    let listener = UnixListener::from(fd);
+    if listener.local_addr().is_ok() {
+        listener.set_nonblocking(true)?;
+        return Ok(ActivatedListener::Unix(listener));
+    }
+    let listener = TcpListener::from(OwnedFd::from(listener));
+    if listener.local_addr().is_ok() {
+        listener.set_nonblocking(true)?;
+        return Ok(ActivatedListener::Tcp(listener));
+    }
+    Err("The socket passed by systemd is neither a TCP nor a Unix socket".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_listen_fds() {
+        assert_eq!(listen_fds(Some("42"), Some("1"), 42).unwrap(), 1);
+        assert_eq!(listen_fds(None, Some("2"), 42).unwrap(), 2);
+        assert_eq!(
+            listen_fds(None, None, 42).unwrap_err().to_string(),
+            "No sockets passed by systemd (LISTEN_FDS is not set)"
+        );
+        assert_eq!(
+            listen_fds(Some("43"), Some("1"), 42).unwrap_err().to_string(),
+            "The sockets passed by systemd are meant for process 43"
+        );
+        assert_eq!(
+            listen_fds(Some("42"), Some("0"), 42).unwrap_err().to_string(),
+            "No sockets passed by systemd"
+        );
+        assert_eq!(
+            listen_fds(Some("42"), Some("foo"), 42).unwrap_err().to_string(),
+            "Invalid LISTEN_FDS environment variable: foo"
+        );
+    }
+}
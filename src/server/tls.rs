@@ -0,0 +1,264 @@
+//! Native TLS support through rustls.
+
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock, Weak};
+use std::time::{Duration, SystemTime};
+
+use axum::extract::connect_info::Connected;
+use axum::serve::{IncomingStream, Listener};
+use log::{debug, error, info};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::server::{ClientHello, ResolvesServerCert};
+use tokio_rustls::rustls::sign::CertifiedKey;
+use tokio_rustls::rustls::{crypto, ServerConfig};
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+
+use super::listener::RemoteAddr;
+use crate::errors::SpaceapiServerError;
+
+/// How often the certificate and key files are checked for changes.
+const RELOAD_INTERVAL: Duration = Duration::from_secs(60);
+
+/// How long a client may take to complete the TLS handshake.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|meta| meta.modified()).ok()
+}
+
+/// Read a PEM encoded certificate chain and private key.
+fn load_certified_key(cert_path: &Path, key_path: &Path) -> Result<CertifiedKey, SpaceapiServerError> {
+    let certs = CertificateDer::pem_file_iter(cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("Could not read certificate file {}: {}", cert_path.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!("No certificate found in {}", cert_path.display()).into());
+    }
+    let key = PrivateKeyDer::from_pem_file(key_path)
+        .map_err(|e| format!("Could not read key file {}: {}", key_path.display(), e))?;
+    let key = crypto::ring::sign::any_supported_type(&key)
+        .map_err(|e| format!("Unsupported key in {}: {}", key_path.display(), e))?;
+    Ok(CertifiedKey::new(certs, key))
+}
+
+/// Serves the current certificate and replaces it when the files change.
+#[derive(Debug)]
+struct ReloadingResolver {
+    cert_path: PathBuf,
+    key_path: PathBuf,
+    modified: RwLock<(Option<SystemTime>, Option<SystemTime>)>,
+    certified_key: RwLock<Arc<CertifiedKey>>,
+}
+
+impl ReloadingResolver {
+    fn new(cert_path: &Path, key_path: &Path) -> Result<ReloadingResolver, SpaceapiServerError> {
+        let modified = (modified(cert_path), modified(key_path));
+        Ok(ReloadingResolver {
+            certified_key: RwLock::new(Arc::new(load_certified_key(cert_path, key_path)?)),
+            cert_path: cert_path.to_owned(),
+            key_path: key_path.to_owned(),
+            modified: RwLock::new(modified),
+        })
+    }
+
+    /// Reload the certificate if one of the files changed. Invalid files are
+    /// rejected and the previous certificate is kept.
+    fn reload_if_changed(&self) {
+        let modified = (modified(&self.cert_path), modified(&self.key_path));
+        {
+            let mut last_modified = self.modified.write().unwrap_or_else(|e| e.into_inner());
+            if *last_modified == modified {
+                return;
+            }
+            *last_modified = modified;
+        }
+        match load_certified_key(&self.cert_path, &self.key_path) {
+            Ok(certified_key) => {
+                *self.certified_key.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(certified_key);
+                info!("Reloaded TLS certificate {}", self.cert_path.display());
+            }
+            Err(e) => error!("{}, keeping the previous certificate", e),
+        }
+    }
+
+    /// Poll the files for changes. The task stops as soon as the resolver
+    /// has been dropped.
+    fn watch(resolver: Weak<ReloadingResolver>) {
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(RELOAD_INTERVAL).await;
+                match resolver.upgrade() {
+                    Some(resolver) => resolver.reload_if_changed(),
+                    None => break,
+                }
+            }
+        });
+    }
+}
+
+impl ResolvesServerCert for ReloadingResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(
+            self.certified_key
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone(),
+        )
+    }
+}
+
+/// A listener that accepts TCP connections and performs the TLS handshake.
+///
+/// Handshakes run in separate tasks, so slow clients don't block others.
+pub(crate) struct TlsListener {
+    connections: mpsc::Receiver<(TlsStream<TcpStream>, SocketAddr)>,
+    local_addr: SocketAddr,
+}
+
+impl TlsListener {
+    /// Wrap a TCP listener, using the certificate and key from the specified
+    /// PEM files. The files are checked for changes regularly, so that
+    /// renewed certificates are picked up without a restart.
+    pub(crate) fn new(
+        listener: TcpListener,
+        cert_path: &Path,
+        key_path: &Path,
+    ) -> Result<TlsListener, SpaceapiServerError> {
+        let resolver = Arc::new(ReloadingResolver::new(cert_path, key_path)?);
+        ReloadingResolver::watch(Arc::downgrade(&resolver));
+        let mut config = ServerConfig::builder_with_provider(Arc::new(crypto::ring::default_provider()))
+            .with_safe_default_protocol_versions()
+            .map_err(|e| format!("Invalid TLS configuration: {}", e))?
+            .with_no_client_auth()
+            .with_cert_resolver(resolver);
+        config.alpn_protocols = vec![b"http/1.1".to_vec()];
+        let acceptor = TlsAcceptor::from(Arc::new(config));
+
+        let local_addr = listener.local_addr()?;
+        let (sender, connections) = mpsc::channel(64);
+        tokio::spawn(async move {
+            loop {
+                let (stream, addr) = tokio::select! {
+                    // The server has been shut down
+                    _ = sender.closed() => break,
+                    result = listener.accept() => match result {
+                        Ok(connection) => connection,
+                        Err(e) => {
+                            error!("Could not accept connection: {}", e);
+                            tokio::time::sleep(Duration::from_millis(100)).await;
+                            continue;
+                        }
+                    },
+                };
+                let acceptor = acceptor.clone();
+                let sender = sender.clone();
+                tokio::spawn(async move {
+                    match tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await {
+                        Ok(Ok(stream)) => {
+                            let _ = sender.send((stream, addr)).await;
+                        }
+                        Ok(Err(e)) => debug!("TLS handshake with {} failed: {}", addr, e),
+                        Err(_) => debug!("TLS handshake with {} timed out", addr),
+                    }
+                });
+            }
+        });
+
+        Ok(TlsListener {
+            connections,
+            local_addr,
+        })
+    }
+}
+
+impl Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        match self.connections.recv().await {
+            Some(connection) => connection,
+            // The accepting task only stops once the listener is dropped
+            None => std::future::pending().await,
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        Ok(self.local_addr)
+    }
+}
+
+impl Connected<IncomingStream<'_, TlsListener>> for RemoteAddr {
+    fn connect_info(stream: IncomingStream<'_, TlsListener>) -> Self {
+        RemoteAddr(Some(*stream.remote_addr()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::env;
+    use std::fs;
+
+    fn write_cert(name: &str, common_name: &str) -> (PathBuf, PathBuf) {
+        let dir = env::temp_dir();
+        let id = std::process::id();
+        let cert_path = dir.join(format!("spaceapi-server-{}-{}.crt", name, id));
+        let key_path = dir.join(format!("spaceapi-server-{}-{}.key", name, id));
+        let cert = rcgen::generate_simple_self_signed(vec![common_name.to_owned()]).unwrap();
+        fs::write(&cert_path, cert.cert.pem()).unwrap();
+        fs::write(&key_path, cert.key_pair.serialize_pem()).unwrap();
+        (cert_path, key_path)
+    }
+
+    fn current_cert(resolver: &ReloadingResolver) -> Vec<u8> {
+        resolver.certified_key.read().unwrap().cert[0].to_vec()
+    }
+
+    #[test]
+    fn invalid_files() {
+        let (cert_path, key_path) = write_cert("invalid", "localhost");
+        fs::write(&key_path, "foo").unwrap();
+        let err = ReloadingResolver::new(&cert_path, &key_path).unwrap_err();
+        assert!(err.to_string().starts_with("Could not read key file"));
+
+        let err = ReloadingResolver::new(Path::new("/nonexistent.crt"), &key_path).unwrap_err();
+        assert!(err.to_string().starts_with("Could not read certificate file"));
+
+        fs::remove_file(cert_path).unwrap();
+        fs::remove_file(key_path).unwrap();
+    }
+
+    #[test]
+    fn reload_changed_files() {
+        let (cert_path, key_path) = write_cert("reload", "localhost");
+        let resolver = ReloadingResolver::new(&cert_path, &key_path).unwrap();
+        let first = current_cert(&resolver);
+
+        // Unchanged files are not reloaded
+        resolver.reload_if_changed();
+        assert_eq!(current_cert(&resolver), first);
+
+        // Invalid files are ignored
+        std::thread::sleep(Duration::from_millis(10));
+        fs::write(&cert_path, "foo").unwrap();
+        resolver.reload_if_changed();
+        assert_eq!(current_cert(&resolver), first);
+
+        // Renewed certificates are picked up
+        std::thread::sleep(Duration::from_millis(10));
+        write_cert("reload", "example.com");
+        resolver.reload_if_changed();
+        assert_ne!(current_cert(&resolver), first);
+
+        fs::remove_file(cert_path).unwrap();
+        fs::remove_file(key_path).unwrap();
+    }
+}
@@ -0,0 +1,224 @@
+//! Rendering of the status in a specific SpaceAPI schema version.
+//!
+//! The status is built once and then mapped to the schema version requested
+//! by the client. The spaceapi crate supports the versions 0.13 and 14, so
+//! those are the versions that can be served.
+
+use crate::api;
+
+/// A SpaceAPI schema version with its own route.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SchemaVersion {
+    V13,
+    V14,
+}
+
+impl SchemaVersion {
+    pub(crate) const ALL: [SchemaVersion; 2] = [SchemaVersion::V13, SchemaVersion::V14];
+
+    /// The route the status is served at in this version.
+    pub(crate) fn route(self) -> &'static str {
+        match self {
+            SchemaVersion::V13 => "/v13",
+            SchemaVersion::V14 => "/v14",
+        }
+    }
+
+    /// The route name used in the metrics.
+    pub(crate) fn name(self) -> &'static str {
+        &self.route()[1..]
+    }
+
+    /// Map the fields of the status to this version. Fails if the status
+    /// lacks data the version requires.
+    pub(crate) fn convert(self, status: &mut api::Status) -> Result<(), &'static str> {
+        match self {
+            SchemaVersion::V13 => to_v13(status),
+            SchemaVersion::V14 => {
+                to_v14(status);
+                Ok(())
+            }
+        }
+    }
+}
+
+/// Map the status to the v0.13 schema.
+fn to_v13(status: &mut api::Status) -> Result<(), &'static str> {
+    status.api = Some("0.13".into());
+    status.api_compatibility = None;
+
+    // Added in v14
+    status.location.timezone = None;
+    status.links = None;
+    status.membership_plans = None;
+    let contact = &mut status.contact;
+    contact.jabber = contact.jabber.take().or_else(|| contact.xmpp.take());
+    contact.xmpp = None;
+    contact.mastodon = None;
+    contact.matrix = None;
+    contact.gopher = None;
+    for keymaster in contact.keymasters.iter_mut().flatten() {
+        keymaster.xmpp = None;
+        keymaster.mastodon = None;
+    }
+
+    // Required in v0.13
+    if let Some(spacefed) = &mut status.spacefed {
+        spacefed.spacephone.get_or_insert(false);
+    }
+    status.state.get_or_insert_with(Default::default);
+    if status.issue_report_channels.is_empty() {
+        let contact = &status.contact;
+        let channels = [
+            (contact.issue_mail.is_some(), api::IssueReportChannel::IssueMail),
+            (contact.email.is_some(), api::IssueReportChannel::Email),
+            (contact.ml.is_some(), api::IssueReportChannel::Ml),
+            (contact.twitter.is_some(), api::IssueReportChannel::Twitter),
+        ];
+        status.issue_report_channels = channels
+            .into_iter()
+            .filter(|(available, _)| *available)
+            .map(|(_, channel)| channel)
+            .collect();
+    }
+    if status.issue_report_channels.is_empty() {
+        return Err("The v0.13 schema requires an issue report channel \
+             (contact.issue_mail, email, ml or twitter)");
+    }
+    Ok(())
+}
+
+/// Map the status to the v14 schema.
+fn to_v14(status: &mut api::Status) {
+    status.api = None;
+    status.api_compatibility = Some(vec![api::ApiVersion::V14]);
+
+    // Renamed in v14
+    let contact = &mut status.contact;
+    contact.xmpp = contact.xmpp.take().or_else(|| contact.jabber.take());
+    contact.jabber = None;
+
+    // Removed in v14
+    contact.google = None;
+    status.radio_show = None;
+    status.issue_report_channels.clear();
+    if let Some(spacefed) = &mut status.spacefed {
+        spacefed.spacephone = None;
+    }
+
+    // The state is optional in v14, but must be known if present
+    if status.state.as_ref().is_some_and(|state| state.open.is_none()) {
+        status.state = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn v13_status() -> api::Status {
+        api::StatusBuilder::v0_13("foo")
+            .logo("https://example.com/logo.png")
+            .url("https://example.com/")
+            .location(api::Location::default())
+            .contact(api::Contact {
+                jabber: Some("foo@example.com".into()),
+                google: Some(api::GoogleContact {
+                    plus: Some("foo".into()),
+                }),
+                ..Default::default()
+            })
+            .spacefed(api::Spacefed {
+                spacenet: true,
+                spacesaml: false,
+                spacephone: Some(true),
+            })
+            .state(api::State::default())
+            .add_issue_report_channel(api::IssueReportChannel::Email)
+            .build()
+            .unwrap()
+    }
+
+    fn v14_status() -> api::Status {
+        api::StatusBuilder::v14("foo")
+            .logo("https://example.com/logo.png")
+            .url("https://example.com/")
+            .location(api::Location {
+                timezone: Some("Europe/Zurich".into()),
+                ..Default::default()
+            })
+            .contact(api::Contact {
+                email: Some("hi@example.com".into()),
+                xmpp: Some("foo@example.com".into()),
+                matrix: Some("#foo:example.com".into()),
+                ..Default::default()
+            })
+            .spacefed(api::Spacefed {
+                spacenet: true,
+                spacesaml: false,
+                spacephone: None,
+            })
+            .add_link(api::Link {
+                name: "Wiki".into(),
+                description: None,
+                url: "https://wiki.example.com/".into(),
+            })
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn v14_to_v13() {
+        let mut status = v14_status();
+        SchemaVersion::V13.convert(&mut status).unwrap();
+        assert_eq!(status.api.as_deref(), Some("0.13"));
+        assert_eq!(status.api_compatibility, None);
+        assert_eq!(status.location.timezone, None);
+        assert_eq!(status.links, None);
+        assert_eq!(status.contact.jabber.as_deref(), Some("foo@example.com"));
+        assert_eq!(status.contact.xmpp, None);
+        assert_eq!(status.contact.matrix, None);
+        assert_eq!(status.spacefed.unwrap().spacephone, Some(false));
+        assert_eq!(status.state, Some(api::State::default()));
+        assert_eq!(status.issue_report_channels, vec![api::IssueReportChannel::Email]);
+    }
+
+    #[test]
+    fn v13_to_v14() {
+        let mut status = v13_status();
+        SchemaVersion::V14.convert(&mut status).unwrap();
+        assert_eq!(status.api, None);
+        assert_eq!(status.api_compatibility, Some(vec![api::ApiVersion::V14]));
+        assert_eq!(status.contact.xmpp.as_deref(), Some("foo@example.com"));
+        assert_eq!(status.contact.jabber, None);
+        assert_eq!(status.contact.google, None);
+        assert_eq!(status.spacefed.unwrap().spacephone, None);
+        assert!(status.issue_report_channels.is_empty());
+        assert_eq!(status.state, None);
+    }
+
+    #[test]
+    fn v14_with_known_state() {
+        let mut status = v13_status();
+        status.state = Some(api::State {
+            open: Some(true),
+            ..Default::default()
+        });
+        SchemaVersion::V14.convert(&mut status).unwrap();
+        assert_eq!(status.state.unwrap().open, Some(true));
+    }
+
+    #[test]
+    fn unchanged_in_same_version() {
+        let mut status = v14_status();
+        SchemaVersion::V14.convert(&mut status).unwrap();
+        assert_eq!(status, v14_status());
+    }
+
+    #[test]
+    fn v13_without_issue_report_channel() {
+        let mut status = v14_status();
+        status.contact.email = None;
+        assert!(SchemaVersion::V13.convert(&mut status).is_err());
+    }
+}
@@ -0,0 +1,437 @@
+//! Storage backends for dynamic data like sensor values.
+//!
+//! The server accesses its data through the async [`Store`](trait.Store.html)
+//! trait. Two implementations are included:
+//!
+//! - [`RedisStore`](struct.RedisStore.html), an async Redis client. This is
+//!   what the server uses when you call
+//!   [`SpaceapiServerBuilder::redis_connection_info`](../struct.SpaceapiServerBuilder.html#method.redis_connection_info).
+//! - `r2d2::Pool<redis::Client>`, a blocking Redis connection pool. Commands
+//!   are run on a thread dedicated to blocking operations. This is what the
+//!   server uses when you call
+//!   [`SpaceapiServerBuilder::redis_pool`](../struct.SpaceapiServerBuilder.html#method.redis_pool).
+//! - [`MemoryStore`](struct.MemoryStore.html), which keeps all data in
+//!   memory, e.g. for tests.
+
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+
+use async_trait::async_trait;
+use quick_error::quick_error;
+use redis::aio::ConnectionManager;
+use redis::{IntoConnectionInfo, RedisError};
+use tokio::sync::OnceCell;
+
+use crate::types::RedisPool;
+
+mod memory;
+
+pub use self::memory::MemoryStore;
+
+/// How long to wait for a connection to Redis.
+const CONNECTION_TIMEOUT: Duration = Duration::from_secs(1);
+
+quick_error! {
+    /// A ``StoreError`` wraps problems that can occur when accessing a store.
+    #[derive(Debug)]
+    pub enum StoreError {
+        /// Redis error
+        Redis(err: RedisError) {
+            from()
+            display("Redis error: {}", err)
+            source(err)
+        }
+        /// R2d2 connection pool error
+        R2d2(err: r2d2::Error) {
+            from()
+            display("Connection pool error: {}", err)
+            source(err)
+        }
+        /// The store did not respond in time
+        Timeout {
+            display("Store operation timed out")
+        }
+        /// The store does not support the operation
+        Unsupported(operation: &'static str) {
+            display("The store does not support {}", operation)
+        }
+        /// Another error happened
+        Other(err: String) {
+            display("{}", err)
+        }
+    }
+}
+
+/// An async key-value store holding the dynamic data of the server.
+#[async_trait]
+pub trait Store: Send + Sync {
+    /// Return the values of the specified keys, in the same order. Keys that
+    /// are not set result in a `None` value.
+    async fn get_values(&self, keys: &[&str]) -> Result<Vec<Option<String>>, StoreError>;
+
+    /// Set the value of the specified key.
+    async fn set_value(&self, key: &str, value: &str) -> Result<(), StoreError>;
+
+    /// Check that the store is reachable.
+    async fn ping(&self) -> Result<(), StoreError>;
+
+    /// Atomically set the value of the specified key and return the
+    /// previous value, `None` if the key was not set.
+    ///
+    /// Used to record the state changes in the event log. The default
+    /// implementation returns a `StoreError::Unsupported` error.
+    async fn swap_value(&self, key: &str, value: &str) -> Result<Option<String>, StoreError> {
+        let _ = (key, value);
+        Err(StoreError::Unsupported("swapping values"))
+    }
+
+    /// Return all fields of the hash stored at the specified key. A key
+    /// that is not set results in an empty hash.
+    ///
+    /// Hashes are used for the presence list. The default implementation
+    /// returns a `StoreError::Unsupported` error.
+    async fn get_hash(&self, key: &str) -> Result<HashMap<String, String>, StoreError> {
+        let _ = key;
+        Err(StoreError::Unsupported("hashes"))
+    }
+
+    /// Set a field of the hash stored at the specified key.
+    async fn set_hash_field(&self, key: &str, field: &str, value: &str) -> Result<(), StoreError> {
+        let _ = (key, field, value);
+        Err(StoreError::Unsupported("hashes"))
+    }
+
+    /// Delete fields of the hash stored at the specified key. Fields that
+    /// are not set are ignored.
+    async fn delete_hash_fields(&self, key: &str, fields: &[&str]) -> Result<(), StoreError> {
+        let _ = (key, fields);
+        Err(StoreError::Unsupported("hashes"))
+    }
+
+    /// Delete a field of the hash stored at the specified key, but only if
+    /// it still has the specified value. Return whether it was deleted.
+    async fn delete_hash_field_if(&self, key: &str, field: &str, value: &str) -> Result<bool, StoreError> {
+        let _ = (key, field, value);
+        Err(StoreError::Unsupported("hashes"))
+    }
+
+    /// Prepend a value to the list stored at the specified key, keeping at
+    /// most `max_len` values. A `max_len` of 0 keeps all values.
+    ///
+    /// Lists are used for the event log. The default implementation returns
+    /// a `StoreError::Unsupported` error.
+    async fn push_to_list(&self, key: &str, value: &str, max_len: usize) -> Result<(), StoreError> {
+        let _ = (key, value, max_len);
+        Err(StoreError::Unsupported("lists"))
+    }
+
+    /// Return the first `count` values of the list stored at the specified
+    /// key, i.e. the most recently pushed ones. A key that is not set
+    /// results in an empty list.
+    async fn get_list(&self, key: &str, count: usize) -> Result<Vec<String>, StoreError> {
+        let _ = (key, count);
+        Err(StoreError::Unsupported("lists"))
+    }
+}
+
+/// Return the time in seconds since the Unix epoch, as timestamps are kept in
+/// the store.
+pub(crate) fn unix_time(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since| since.as_secs())
+}
+
+/// Build the command to prepend a value to a list and trim it.
+fn push_to_list_pipeline(key: &str, value: &str, max_len: usize) -> redis::Pipeline {
+    let mut pipeline = redis::pipe();
+    pipeline.atomic().cmd("LPUSH").arg(key).arg(value).ignore();
+    if max_len > 0 {
+        pipeline.cmd("LTRIM").arg(key).arg(0).arg(max_len - 1).ignore();
+    }
+    pipeline
+}
+
+/// Build the script deleting a hash field if it has the expected value.
+fn delete_hash_field_if_script() -> redis::Script {
+    redis::Script::new(
+        r"if redis.call('HGET', KEYS[1], ARGV[1]) == ARGV[2] then
+            return redis.call('HDEL', KEYS[1], ARGV[1])
+        end
+        return 0",
+    )
+}
+
+/// The index of the last value to return from a list, for `LRANGE`.
+fn last_index(count: usize) -> isize {
+    isize::try_from(count).unwrap_or(isize::MAX) - 1
+}
+
+/// An async Redis store.
+///
+/// The connection is established lazily on first use and re-established
+/// automatically if it breaks. This allows the server to start even if Redis
+/// is not reachable yet.
+pub struct RedisStore {
+    client: redis::Client,
+    connection: OnceCell<ConnectionManager>,
+}
+
+impl RedisStore {
+    /// Create a new store for the specified connection info, e.g. a
+    /// connection string like `redis://127.0.0.1/`.
+    pub fn new<R: IntoConnectionInfo>(connection_info: R) -> Result<RedisStore, StoreError> {
+        Ok(RedisStore {
+            client: redis::Client::open(connection_info)?,
+            connection: OnceCell::new(),
+        })
+    }
+
+    async fn connection(&self) -> Result<ConnectionManager, StoreError> {
+        let connect = self
+            .connection
+            .get_or_try_init(|| ConnectionManager::new(self.client.clone()));
+        match tokio::time::timeout(CONNECTION_TIMEOUT, connect).await {
+            Ok(connection) => Ok(connection?.clone()),
+            Err(_) => Err(StoreError::Timeout),
+        }
+    }
+}
+
+#[async_trait]
+impl Store for RedisStore {
+    async fn get_values(&self, keys: &[&str]) -> Result<Vec<Option<String>>, StoreError> {
+        if keys.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut conn = self.connection().await?;
+        Ok(redis::cmd("MGET").arg(keys).query_async(&mut conn).await?)
+    }
+
+    async fn set_value(&self, key: &str, value: &str) -> Result<(), StoreError> {
+        let mut conn = self.connection().await?;
+        redis::cmd("SET")
+            .arg(key)
+            .arg(value)
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn ping(&self) -> Result<(), StoreError> {
+        let mut conn = self.connection().await?;
+        redis::cmd("PING").query_async::<_, ()>(&mut conn).await?;
+        Ok(())
+    }
+
+    async fn swap_value(&self, key: &str, value: &str) -> Result<Option<String>, StoreError> {
+        let mut conn = self.connection().await?;
+        Ok(redis::cmd("GETSET")
+            .arg(key)
+            .arg(value)
+            .query_async(&mut conn)
+            .await?)
+    }
+
+    async fn get_hash(&self, key: &str) -> Result<HashMap<String, String>, StoreError> {
+        let mut conn = self.connection().await?;
+        Ok(redis::cmd("HGETALL").arg(key).query_async(&mut conn).await?)
+    }
+
+    async fn set_hash_field(&self, key: &str, field: &str, value: &str) -> Result<(), StoreError> {
+        let mut conn = self.connection().await?;
+        redis::cmd("HSET")
+            .arg(key)
+            .arg(field)
+            .arg(value)
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_hash_fields(&self, key: &str, fields: &[&str]) -> Result<(), StoreError> {
+        if fields.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.connection().await?;
+        redis::cmd("HDEL")
+            .arg(key)
+            .arg(fields)
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete_hash_field_if(&self, key: &str, field: &str, value: &str) -> Result<bool, StoreError> {
+        let mut conn = self.connection().await?;
+        let deleted: u64 = delete_hash_field_if_script()
+            .key(key)
+            .arg(field)
+            .arg(value)
+            .invoke_async(&mut conn)
+            .await?;
+        Ok(deleted > 0)
+    }
+
+    async fn push_to_list(&self, key: &str, value: &str, max_len: usize) -> Result<(), StoreError> {
+        let mut conn = self.connection().await?;
+        push_to_list_pipeline(key, value, max_len)
+            .query_async::<_, ()>(&mut conn)
+            .await?;
+        Ok(())
+    }
+
+    async fn get_list(&self, key: &str, count: usize) -> Result<Vec<String>, StoreError> {
+        if count == 0 {
+            return Ok(vec![]);
+        }
+        let mut conn = self.connection().await?;
+        Ok(redis::cmd("LRANGE")
+            .arg(key)
+            .arg(0)
+            .arg(last_index(count))
+            .query_async(&mut conn)
+            .await?)
+    }
+}
+
+/// Run a blocking store operation on a thread dedicated to blocking
+/// operations.
+async fn blocking<T, F>(f: F) -> Result<T, StoreError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, StoreError> + Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .unwrap_or_else(|e| Err(StoreError::Other(format!("Store operation failed: {}", e))))
+}
+
+#[async_trait]
+impl Store for RedisPool {
+    async fn get_values(&self, keys: &[&str]) -> Result<Vec<Option<String>>, StoreError> {
+        if keys.is_empty() {
+            return Ok(vec![]);
+        }
+        let pool = self.clone();
+        let keys: Vec<String> = keys.iter().map(|&key| key.into()).collect();
+        blocking(move || {
+            let mut conn = pool.get()?;
+            Ok(redis::cmd("MGET").arg(keys).query(&mut *conn)?)
+        })
+        .await
+    }
+
+    async fn set_value(&self, key: &str, value: &str) -> Result<(), StoreError> {
+        let pool = self.clone();
+        let (key, value) = (key.to_owned(), value.to_owned());
+        blocking(move || {
+            let mut conn = pool.get()?;
+            redis::cmd("SET").arg(key).arg(value).query::<()>(&mut *conn)?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn ping(&self) -> Result<(), StoreError> {
+        let pool = self.clone();
+        blocking(move || {
+            let mut conn = pool.get()?;
+            redis::cmd("PING").query::<()>(&mut *conn)?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn swap_value(&self, key: &str, value: &str) -> Result<Option<String>, StoreError> {
+        let pool = self.clone();
+        let (key, value) = (key.to_owned(), value.to_owned());
+        blocking(move || {
+            let mut conn = pool.get()?;
+            Ok(redis::cmd("GETSET").arg(key).arg(value).query(&mut *conn)?)
+        })
+        .await
+    }
+
+    async fn get_hash(&self, key: &str) -> Result<HashMap<String, String>, StoreError> {
+        let pool = self.clone();
+        let key = key.to_owned();
+        blocking(move || {
+            let mut conn = pool.get()?;
+            Ok(redis::cmd("HGETALL").arg(key).query(&mut *conn)?)
+        })
+        .await
+    }
+
+    async fn set_hash_field(&self, key: &str, field: &str, value: &str) -> Result<(), StoreError> {
+        let pool = self.clone();
+        let (key, field, value) = (key.to_owned(), field.to_owned(), value.to_owned());
+        blocking(move || {
+            let mut conn = pool.get()?;
+            redis::cmd("HSET")
+                .arg(key)
+                .arg(field)
+                .arg(value)
+                .query::<()>(&mut *conn)?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn delete_hash_fields(&self, key: &str, fields: &[&str]) -> Result<(), StoreError> {
+        if fields.is_empty() {
+            return Ok(());
+        }
+        let pool = self.clone();
+        let key = key.to_owned();
+        let fields: Vec<String> = fields.iter().map(|&field| field.into()).collect();
+        blocking(move || {
+            let mut conn = pool.get()?;
+            redis::cmd("HDEL").arg(key).arg(fields).query::<()>(&mut *conn)?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn delete_hash_field_if(&self, key: &str, field: &str, value: &str) -> Result<bool, StoreError> {
+        let pool = self.clone();
+        let (key, field, value) = (key.to_owned(), field.to_owned(), value.to_owned());
+        blocking(move || {
+            let mut conn = pool.get()?;
+            let deleted: u64 = delete_hash_field_if_script()
+                .key(key)
+                .arg(field)
+                .arg(value)
+                .invoke(&mut *conn)?;
+            Ok(deleted > 0)
+        })
+        .await
+    }
+
+    async fn push_to_list(&self, key: &str, value: &str, max_len: usize) -> Result<(), StoreError> {
+        let pool = self.clone();
+        let pipeline = push_to_list_pipeline(key, value, max_len);
+        blocking(move || {
+            let mut conn = pool.get()?;
+            pipeline.query::<()>(&mut *conn)?;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn get_list(&self, key: &str, count: usize) -> Result<Vec<String>, StoreError> {
+        if count == 0 {
+            return Ok(vec![]);
+        }
+        let pool = self.clone();
+        let key = key.to_owned();
+        blocking(move || {
+            let mut conn = pool.get()?;
+            Ok(redis::cmd("LRANGE")
+                .arg(key)
+                .arg(0)
+                .arg(last_index(count))
+                .query(&mut *conn)?)
+        })
+        .await
+    }
+}
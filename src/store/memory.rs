@@ -0,0 +1,170 @@
+//! A store keeping all data in memory.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+
+use async_trait::async_trait;
+
+use super::{Store, StoreError};
+
+#[derive(Debug, Default)]
+struct Data {
+    values: HashMap<String, String>,
+    hashes: HashMap<String, HashMap<String, String>>,
+    lists: HashMap<String, Vec<String>>,
+}
+
+/// A store keeping all data in memory, e.g. for tests or demos. The data is
+/// lost when the server stops.
+///
+/// ```
+/// use spaceapi_server::store::MemoryStore;
+/// # use spaceapi_server::api;
+/// # let status = api::StatusBuilder::v14("aa")
+/// #     .logo("https://example.com/logo.png")
+/// #     .url("https://example.com/")
+/// #     .location(api::Location::default())
+/// #     .contact(api::Contact::default())
+/// #     .build()
+/// #     .unwrap();
+///
+/// let server = spaceapi_server::SpaceapiServerBuilder::new(status)
+///     .store(MemoryStore::new())
+///     .build()
+///     .unwrap();
+/// ```
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    data: Mutex<Data>,
+}
+
+impl MemoryStore {
+    /// Create an empty store.
+    pub fn new() -> MemoryStore {
+        MemoryStore::default()
+    }
+
+    fn data(&self) -> MutexGuard<'_, Data> {
+        self.data.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait]
+impl Store for MemoryStore {
+    async fn get_values(&self, keys: &[&str]) -> Result<Vec<Option<String>>, StoreError> {
+        let data = self.data();
+        Ok(keys.iter().map(|&key| data.values.get(key).cloned()).collect())
+    }
+
+    async fn set_value(&self, key: &str, value: &str) -> Result<(), StoreError> {
+        self.data().values.insert(key.into(), value.into());
+        Ok(())
+    }
+
+    async fn ping(&self) -> Result<(), StoreError> {
+        Ok(())
+    }
+
+    async fn swap_value(&self, key: &str, value: &str) -> Result<Option<String>, StoreError> {
+        Ok(self.data().values.insert(key.into(), value.into()))
+    }
+
+    async fn get_hash(&self, key: &str) -> Result<HashMap<String, String>, StoreError> {
+        Ok(self.data().hashes.get(key).cloned().unwrap_or_default())
+    }
+
+    async fn set_hash_field(&self, key: &str, field: &str, value: &str) -> Result<(), StoreError> {
+        self.data()
+            .hashes
+            .entry(key.into())
+            .or_default()
+            .insert(field.into(), value.into());
+        Ok(())
+    }
+
+    async fn delete_hash_fields(&self, key: &str, fields: &[&str]) -> Result<(), StoreError> {
+        if let Some(hash) = self.data().hashes.get_mut(key) {
+            for field in fields {
+                hash.remove(*field);
+            }
+        }
+        Ok(())
+    }
+
+    async fn delete_hash_field_if(&self, key: &str, field: &str, value: &str) -> Result<bool, StoreError> {
+        match self.data().hashes.get_mut(key) {
+            Some(hash) if hash.get(field).is_some_and(|v| v == value) => {
+                hash.remove(field);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn push_to_list(&self, key: &str, value: &str, max_len: usize) -> Result<(), StoreError> {
+        let mut data = self.data();
+        let list = data.lists.entry(key.into()).or_default();
+        list.insert(0, value.into());
+        if max_len > 0 {
+            list.truncate(max_len);
+        }
+        Ok(())
+    }
+
+    async fn get_list(&self, key: &str, count: usize) -> Result<Vec<String>, StoreError> {
+        let data = self.data();
+        Ok(data
+            .lists
+            .get(key)
+            .map(|list| list.iter().take(count).cloned().collect())
+            .unwrap_or_default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn values() {
+        let store = MemoryStore::new();
+        store.set_value("a", "1").await.unwrap();
+        assert_eq!(
+            store.get_values(&["a", "b"]).await.unwrap(),
+            vec![Some("1".into()), None]
+        );
+        assert_eq!(store.swap_value("a", "2").await.unwrap().as_deref(), Some("1"));
+        assert_eq!(store.swap_value("b", "3").await.unwrap(), None);
+        assert_eq!(
+            store.get_values(&["a", "b"]).await.unwrap(),
+            vec![Some("2".into()), Some("3".into())]
+        );
+    }
+
+    #[tokio::test]
+    async fn hashes() {
+        let store = MemoryStore::new();
+        store.set_hash_field("h", "a", "1").await.unwrap();
+        store.set_hash_field("h", "b", "2").await.unwrap();
+        store.delete_hash_fields("h", &["a", "c"]).await.unwrap();
+        assert_eq!(
+            store.get_hash("h").await.unwrap(),
+            [("b".into(), "2".into())].into()
+        );
+        assert!(!store.delete_hash_field_if("h", "b", "1").await.unwrap());
+        assert!(store.delete_hash_field_if("h", "b", "2").await.unwrap());
+        assert!(!store.delete_hash_field_if("h", "b", "2").await.unwrap());
+        assert!(store.get_hash("other").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn lists() {
+        let store = MemoryStore::new();
+        for value in ["1", "2", "3"] {
+            store.push_to_list("l", value, 2).await.unwrap();
+        }
+        assert_eq!(store.get_list("l", 10).await.unwrap(), vec!["3", "2"]);
+        assert_eq!(store.get_list("l", 1).await.unwrap(), vec!["3"]);
+        assert!(store.get_list("other", 10).await.unwrap().is_empty());
+    }
+}
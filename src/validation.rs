@@ -0,0 +1,211 @@
+//! Validation of the status against the bundled SpaceAPI JSON schemas.
+
+use std::sync::OnceLock;
+
+use jsonschema::Validator;
+use log::warn;
+use serde_json::Value;
+
+use crate::api;
+use crate::errors::SpaceapiServerError;
+
+static SCHEMA_V13: &str = include_str!("../schemas/13.json");
+static SCHEMA_V14: &str = include_str!("../schemas/14.json");
+
+/// How the served status is checked against the SpaceAPI schema.
+///
+/// The status is validated against the schema of every version it
+/// declares, i.e. v0.13 if the `api` field is `"0.13"` and v14 if
+/// `api_compatibility` contains `"14"`. Statuses served at the versioned
+/// routes (`/v13`, `/v14`) are validated against that version only.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ValidationMode {
+    /// Don't validate the status.
+    #[default]
+    Off,
+    /// Log a warning for every violation, but serve the status anyway.
+    Log,
+    /// Log the violations and respond with `500 Internal Server Error`.
+    Strict,
+}
+
+fn validator(cell: &'static OnceLock<Validator>, schema: &str) -> &'static Validator {
+    cell.get_or_init(|| {
+        let schema = serde_json::from_str(schema).expect("Bundled schema is not valid JSON");
+        jsonschema::draft7::new(&schema).expect("Bundled schema is invalid")
+    })
+}
+
+/// Return the schemas of all versions declared by the status.
+fn declared_schemas(status: &Value) -> Vec<(&'static str, &'static Validator)> {
+    static V13: OnceLock<Validator> = OnceLock::new();
+    static V14: OnceLock<Validator> = OnceLock::new();
+
+    let mut schemas = vec![];
+    if status["api"] == "0.13" {
+        schemas.push(("v0.13", validator(&V13, SCHEMA_V13)));
+    }
+    let declares_v14 = status["api_compatibility"]
+        .as_array()
+        .is_some_and(|versions| versions.iter().any(|version| version == "14"));
+    if declares_v14 {
+        schemas.push(("v14", validator(&V14, SCHEMA_V14)));
+    }
+    schemas
+}
+
+/// Validate the status against the schemas of all versions it declares.
+pub(crate) fn validate(status: &api::Status) -> Result<(), SpaceapiServerError> {
+    let status = serde_json::to_value(status).map_err(|e| format!("Could not serialize status: {}", e))?;
+    validate_json(&status)
+}
+
+/// Validate the serialized status against the schemas of all versions it
+/// declares.
+///
+/// The `versions` field added by the server predates the `ext_` prefix the
+/// schemas require for custom fields. It is kept for existing clients and
+/// not validated.
+fn validate_json(status: &Value) -> Result<(), SpaceapiServerError> {
+    let without_versions;
+    let status = match status.get("versions") {
+        Some(_) => {
+            let mut status = status.clone();
+            status.as_object_mut().map(|fields| fields.remove("versions"));
+            without_versions = status;
+            &without_versions
+        }
+        None => status,
+    };
+    let schemas = declared_schemas(status);
+    if schemas.is_empty() {
+        return Err(SpaceapiServerError::InvalidStatus(
+            "No supported SpaceAPI version declared".into(),
+        ));
+    }
+    let errors: Vec<String> = schemas
+        .into_iter()
+        .flat_map(|(version, validator)| {
+            validator.iter_errors(status).map(move |e| {
+                let path = e.instance_path.to_string();
+                let path = if path.is_empty() { "/".into() } else { path };
+                format!("{} {}: {}", version, path, e)
+            })
+        })
+        .collect();
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(SpaceapiServerError::InvalidStatus(errors.join("; ")))
+    }
+}
+
+impl ValidationMode {
+    /// Validate the serialized status according to the mode. Only returns
+    /// an error in strict mode.
+    pub(crate) fn check(self, status: &Value) -> Result<(), SpaceapiServerError> {
+        if self == ValidationMode::Off {
+            return Ok(());
+        }
+        let result = validate_json(status);
+        if let Err(e) = &result {
+            warn!("{}", e);
+        }
+        match self {
+            ValidationMode::Strict => result,
+            _ => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status() -> api::Status {
+        api::StatusBuilder::mixed("foo")
+            .logo("https://example.com/logo.png")
+            .url("https://example.com/")
+            .location(api::Location::default())
+            .contact(api::Contact {
+                email: Some("hi@example.com".into()),
+                ..Default::default()
+            })
+            .state(api::State {
+                open: Some(true),
+                ..Default::default()
+            })
+            .add_issue_report_channel(api::IssueReportChannel::Email)
+            .add_extension("foo", Value::Bool(true))
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn valid_status() {
+        validate(&status()).unwrap();
+    }
+
+    #[test]
+    fn invalid_status() {
+        let mut status = status();
+        status.state = Some(api::State {
+            message: Some("Open until midnight".into()),
+            ..Default::default()
+        });
+        status.issue_report_channels.clear();
+        let err = validate(&status).unwrap_err().to_string();
+        assert!(
+            err.contains("v0.13 /: \"issue_report_channels\" is a required property"),
+            "{}",
+            err
+        );
+        assert!(
+            err.contains("v14 /state/open: null is not of type \"boolean\""),
+            "{}",
+            err
+        );
+        assert!(!err.contains("v0.13 /state"), "{}", err);
+    }
+
+    #[test]
+    fn unknown_fields() {
+        let mut status = status();
+        status.extensions.insert("foo".into(), Value::Null);
+        let err = validate(&status).unwrap_err().to_string();
+        assert!(
+            err.contains("Additional properties are not allowed ('foo' was unexpected)"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn legacy_versions_field() {
+        let mut status = status();
+        status.extensions.insert("versions".into(), Value::Null);
+        validate(&status).unwrap();
+    }
+
+    #[test]
+    fn no_version() {
+        let mut status = status();
+        status.api = None;
+        status.api_compatibility = None;
+        assert_eq!(
+            validate(&status).unwrap_err().to_string(),
+            "Invalid status: No supported SpaceAPI version declared"
+        );
+    }
+
+    #[test]
+    fn modes() {
+        let mut status = status();
+        status.api = None;
+        status.api_compatibility = None;
+        let status = serde_json::to_value(status).unwrap();
+        assert!(ValidationMode::Off.check(&status).is_ok());
+        assert!(ValidationMode::Log.check(&status).is_ok());
+        assert!(ValidationMode::Strict.check(&status).is_err());
+    }
+}
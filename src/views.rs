@@ -0,0 +1,318 @@
+//! Views of the status for different audiences.
+//!
+//! A view hides parts of the status from the clients it applies to, e.g.
+//! the names of the people present from the public internet, while members
+//! on the space network see everything:
+//!
+//! ```
+//! use spaceapi_server::views::View;
+//!
+//! # fn main() -> Result<(), spaceapi_server::SpaceapiServerError> {
+//! let internal = View::new("internal")
+//!     .allow_network("192.168.42.0/24".parse()?)
+//!     .allow_token("secret-token");
+//! let public = View::new("public")
+//!     .hide("sensors.people_now_present.names")
+//!     .hide("sensors.power_consumption");
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! Views are registered with
+//! [`SpaceapiServerBuilder::add_view`](../struct.SpaceapiServerBuilder.html#method.add_view)
+//! and tried in that order. A client gets the first view it matches. The
+//! last view is the one for everybody else, i.e. it must not have networks
+//! or tokens, otherwise building the server fails. This way, no client gets
+//! the full status just because it matches none of the views.
+
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use serde_json::Value;
+
+use crate::auth;
+use crate::errors::SpaceapiServerError;
+
+/// An IP network in CIDR notation, e.g. `192.168.42.0/24` or `fd00::/8`.
+/// A single address matches only itself.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Network {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl Network {
+    /// Return whether the network contains the specified address.
+    /// IPv4-mapped IPv6 addresses are treated as IPv4 addresses.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        match (self.addr, addr.to_canonical()) {
+            (IpAddr::V4(network), IpAddr::V4(addr)) => {
+                prefix_matches(&network.octets(), &addr.octets(), self.prefix_len)
+            }
+            (IpAddr::V6(network), IpAddr::V6(addr)) => {
+                prefix_matches(&network.octets(), &addr.octets(), self.prefix_len)
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Return whether the first `prefix_len` bits of the addresses are equal.
+fn prefix_matches(network: &[u8], addr: &[u8], prefix_len: u8) -> bool {
+    let (bytes, bits) = (usize::from(prefix_len / 8), prefix_len % 8);
+    if network[..bytes] != addr[..bytes] {
+        return false;
+    }
+    bits == 0 || (network[bytes] ^ addr[bytes]) >> (8 - bits) == 0
+}
+
+impl FromStr for Network {
+    type Err = SpaceapiServerError;
+
+    fn from_str(s: &str) -> Result<Network, SpaceapiServerError> {
+        let invalid = || SpaceapiServerError::from(format!("Invalid network: {}", s));
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr.parse().map_err(|_| invalid())?;
+        let max_len = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len.parse().map_err(|_| invalid())?,
+            None => max_len,
+        };
+        if prefix_len > max_len {
+            return Err(invalid());
+        }
+        Ok(Network { addr, prefix_len })
+    }
+}
+
+impl fmt::Display for Network {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+/// A view of the status, see the [module documentation](index.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct View {
+    name: String,
+    networks: Vec<Network>,
+    tokens: Vec<String>,
+    hidden: Vec<String>,
+}
+
+impl View {
+    /// Create a view that applies to all clients and hides nothing.
+    pub fn new<S: Into<String>>(name: S) -> View {
+        View {
+            name: name.into(),
+            networks: vec![],
+            tokens: vec![],
+            hidden: vec![],
+        }
+    }
+
+    /// Apply the view to clients from the specified network.
+    ///
+    /// Behind a reverse proxy, all clients connect from the address of the
+    /// proxy, so use tokens instead.
+    pub fn allow_network(mut self, network: Network) -> Self {
+        self.networks.push(network);
+        self
+    }
+
+    /// Apply the view to clients that send the specified token in an
+    /// `Authorization: Bearer <token>` header.
+    pub fn allow_token<S: Into<String>>(mut self, token: S) -> Self {
+        self.tokens.push(token.into());
+        self
+    }
+
+    /// Hide a field of the status, specified by its path in the SpaceAPI
+    /// JSON format, e.g. `sensors.people_now_present.names`. Paths continue
+    /// into all items of arrays.
+    pub fn hide<S: Into<String>>(mut self, path: S) -> Self {
+        self.hidden.push(path.into());
+        self
+    }
+
+    /// The name of the view.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Return whether the view applies to a client with the specified
+    /// address and bearer token.
+    pub(crate) fn matches(&self, addr: Option<IpAddr>, token: Option<&str>) -> bool {
+        if self.applies_to_all() {
+            return true;
+        }
+        let network_matches = addr.is_some_and(|addr| self.networks.iter().any(|n| n.contains(addr)));
+        let token_matches = auth::token_allowed(&self.tokens, token);
+        network_matches || token_matches
+    }
+
+    /// Return whether the view applies to all clients, i.e. has no networks
+    /// and tokens.
+    fn applies_to_all(&self) -> bool {
+        self.networks.is_empty() && self.tokens.is_empty()
+    }
+
+    /// Return whether the view hides any fields.
+    pub(crate) fn hides_fields(&self) -> bool {
+        !self.hidden.is_empty()
+    }
+
+    /// Remove the hidden fields from the serialized status.
+    pub(crate) fn filter(&self, status: &mut Value) {
+        for path in &self.hidden {
+            let path: Vec<&str> = path.split('.').collect();
+            remove(status, &path);
+        }
+    }
+}
+
+/// Remove the field at the path, descending into all items of arrays.
+fn remove(value: &mut Value, path: &[&str]) {
+    match value {
+        Value::Array(items) => items.iter_mut().for_each(|item| remove(item, path)),
+        Value::Object(fields) => match path {
+            [] => {}
+            [field] => {
+                fields.remove(*field);
+            }
+            [field, rest @ ..] => {
+                if let Some(value) = fields.get_mut(*field) {
+                    remove(value, rest);
+                }
+            }
+        },
+        _ => {}
+    }
+}
+
+/// Check that the last view applies to all clients, so that every client
+/// gets one of the views.
+pub(crate) fn check(views: &[View]) -> Result<(), SpaceapiServerError> {
+    match views.last() {
+        Some(view) if !view.applies_to_all() => Err(format!(
+            "The last view \"{}\" must apply to all clients, i.e. have no networks and tokens",
+            view.name
+        )
+        .into()),
+        _ => Ok(()),
+    }
+}
+
+/// Return the first view that applies to the client. There is always one
+/// if there are any views, see [`check`].
+pub(crate) fn select<'a>(views: &'a [View], addr: Option<IpAddr>, token: Option<&str>) -> Option<&'a View> {
+    views.iter().find(|view| view.matches(addr, token))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    fn ip(addr: &str) -> IpAddr {
+        addr.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_network() {
+        let network: Network = "192.168.42.0/24".parse().unwrap();
+        assert_eq!(network.to_string(), "192.168.42.0/24");
+        assert_eq!("::1".parse::<Network>().unwrap().to_string(), "::1/128");
+        for invalid in [
+            "192.168.42.0/33",
+            "192.168.42/24",
+            "fd00::/129",
+            "foo",
+            "10.0.0.0/",
+        ] {
+            assert_eq!(
+                invalid.parse::<Network>().unwrap_err().to_string(),
+                format!("Invalid network: {}", invalid)
+            );
+        }
+    }
+
+    #[test]
+    fn network_contains() {
+        let network: Network = "192.168.42.0/23".parse().unwrap();
+        assert!(network.contains(ip("192.168.42.1")));
+        assert!(network.contains(ip("192.168.43.255")));
+        assert!(!network.contains(ip("192.168.44.0")));
+        assert!(network.contains(ip("::ffff:192.168.42.1")));
+        assert!(!network.contains(ip("fd00::1")));
+
+        let network: Network = "fd00::/8".parse().unwrap();
+        assert!(network.contains(ip("fd12:3456::1")));
+        assert!(!network.contains(ip("fe80::1")));
+        assert!("0.0.0.0/0".parse::<Network>().unwrap().contains(ip("8.8.8.8")));
+    }
+
+    #[test]
+    fn select_view() {
+        let views = [
+            View::new("internal")
+                .allow_network("10.0.0.0/8".parse().unwrap())
+                .allow_token("secret"),
+            View::new("public").hide("sensors"),
+        ];
+        let selected = |addr, token| select(&views, addr, token).map(View::name);
+        assert_eq!(selected(Some(ip("10.1.2.3")), None), Some("internal"));
+        assert_eq!(selected(Some(ip("8.8.8.8")), Some("secret")), Some("internal"));
+        assert_eq!(selected(Some(ip("8.8.8.8")), Some("guess")), Some("public"));
+        assert_eq!(selected(None, None), Some("public"));
+        assert_eq!(select(&views[..1], None, None), None);
+    }
+
+    #[test]
+    fn check_views() {
+        let internal = View::new("internal").allow_token("secret");
+        let public = View::new("public").hide("sensors");
+        assert!(check(&[]).is_ok());
+        assert!(check(&[internal.clone(), public.clone()]).is_ok());
+        assert_eq!(
+            check(&[public, internal]).unwrap_err().to_string(),
+            "The last view \"internal\" must apply to all clients, i.e. have no networks and tokens"
+        );
+    }
+
+    #[test]
+    fn filter_fields() {
+        let view = View::new("public")
+            .hide("sensors.people_now_present.names")
+            .hide("sensors.power_consumption")
+            .hide("contact.phone")
+            .hide("unknown.field");
+        let mut status = json!({
+            "space": "foo",
+            "contact": { "email": "hi@example.com" },
+            "sensors": {
+                "people_now_present": [
+                    { "value": 2, "names": ["alice", "bob"] },
+                    { "value": 0 }
+                ],
+                "power_consumption": [{ "value": 300, "unit": "W" }]
+            }
+        });
+        view.filter(&mut status);
+        assert_eq!(
+            status,
+            json!({
+                "space": "foo",
+                "contact": { "email": "hi@example.com" },
+                "sensors": {
+                    "people_now_present": [{ "value": 2 }, { "value": 0 }]
+                }
+            })
+        );
+    }
+}
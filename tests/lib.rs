@@ -1,6 +1,12 @@
 use std::io::ErrorKind;
 use std::net::Ipv4Addr;
 use std::net::TcpStream;
+use std::time::Duration;
+
+use axum::body::Body;
+use axum::http::{Request, Response, StatusCode};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tower::ServiceExt;
 
 use spaceapi_server::api;
 use spaceapi_server::{SpaceapiServer, SpaceapiServerBuilder};
@@ -38,8 +44,57 @@ fn get_server(status: api::Status) -> SpaceapiServer {
         .unwrap()
 }
 
-#[test]
-fn server_starts() {
+/// Wait until the server accepts connections on the specified port.
+async fn wait_for_server(ip: Ipv4Addr, port: u16) {
+    for _ in 0..100 {
+        if tokio::net::TcpStream::connect((ip, port)).await.is_ok() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    panic!("Server did not start");
+}
+
+/// Send a raw HTTP request and return the full response.
+async fn request(ip: Ipv4Addr, port: u16, method: &str, path: &str, body: &str) -> String {
+    let mut stream = tokio::net::TcpStream::connect((ip, port)).await.unwrap();
+    let request = format!(
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\
+         Content-Type: application/x-www-form-urlencoded\r\nContent-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+}
+
+/// Send the request to the router and parse the response body as JSON.
+async fn send_json(app: &axum::Router, request: Request<Body>) -> Response<serde_json::Value> {
+    let response = app.clone().oneshot(request).await.unwrap();
+    let (parts, body) = response.into_parts();
+    let body = axum::body::to_bytes(body, usize::MAX).await.unwrap();
+    Response::from_parts(parts, serde_json::from_slice(&body).unwrap())
+}
+
+/// Send a `GET` request with the specified headers to the router and parse
+/// the response body as JSON.
+async fn get_json(app: &axum::Router, uri: &str, headers: &[(&str, &str)]) -> Response<serde_json::Value> {
+    let request = headers
+        .iter()
+        .fold(Request::builder().uri(uri), |request, (name, value)| {
+            request.header(*name, *value)
+        })
+        .body(Body::empty())
+        .unwrap();
+    send_json(app, request).await
+}
+
+#[tokio::test]
+async fn server_starts() {
     //! Test that the spaceapi server starts at all.
 
     // Ip / port for test server
@@ -56,12 +111,936 @@ fn server_starts() {
 
     // Instantiate and start server
     let server = get_server(status);
-    let mut listening = server.serve((ip, port)).unwrap();
+    let shutdown_handle = server.shutdown_handle();
+    let serving = tokio::spawn(server.serve((ip, port)));
 
     // Connecting to server should work now
+    wait_for_server(ip, port).await;
+
+    // Close server
+    assert!(shutdown_handle.shutdown(Duration::from_secs(5)).await);
+    serving.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn server_shuts_down() {
+    //! Test that the spaceapi server stops accepting connections on shutdown.
+
+    // Ip / port for test server
+    let ip = Ipv4Addr::new(127, 0, 0, 1);
+    let port = 3345;
+
+    // Instantiate and start server
+    let server = get_server(get_status());
+    let shutdown_handle = server.shutdown_handle();
+    let serving = tokio::spawn(server.serve((ip, port)));
+    wait_for_server(ip, port).await;
+
+    // Shut down server, there are no in-flight requests
+    assert!(shutdown_handle.shutdown(Duration::from_secs(5)).await);
+    serving.await.unwrap().unwrap();
+
+    // Connection to port should fail now
     let connect_result = TcpStream::connect((ip, port));
-    assert!(connect_result.is_ok());
+    assert!(connect_result.is_err());
+    assert_eq!(connect_result.unwrap_err().kind(), ErrorKind::ConnectionRefused);
+}
+
+#[tokio::test]
+async fn read_and_update() {
+    //! Test the status and sensor update routes.
+
+    // Ip / port for test server
+    let ip = Ipv4Addr::new(127, 0, 0, 1);
+    let port = 3346;
+
+    // Instantiate and start server
+    let server = get_server(get_status());
+    let shutdown_handle = server.shutdown_handle();
+    let serving = tokio::spawn(server.serve((ip, port)));
+    wait_for_server(ip, port).await;
+
+    // The status is served as JSON
+    let response = request(ip, port, "GET", "/", "").await;
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.contains("content-type: application/json; charset=utf-8\r\n"));
+    assert!(response.contains("access-control-allow-origin: *\r\n"));
+    assert!(response.contains(r#""space":"ourspace""#));
+
+    // Updating an unknown sensor fails
+    let response = request(ip, port, "PUT", "/sensors/foo/", "value=42").await;
+    assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    assert!(response.ends_with(r#"{"status":"error","reason":"Unknown sensor: foo"}"#));
+
+    // The value parameter is required
+    let response = request(ip, port, "PUT", "/sensors/foo/", "").await;
+    assert!(response.starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    assert!(response.ends_with(r#"{"status":"error","reason":"\"value\" parameter not specified"}"#));
+
+    // Close server
+    assert!(shutdown_handle.shutdown(Duration::from_secs(5)).await);
+    serving.await.unwrap().unwrap();
+}
+
+#[tokio::test]
+async fn preflight_on_all_routes() {
+    //! Test that CORS preflight requests are answered on every route.
+
+    let app: axum::Router = get_server(get_status()).into_router();
+    for uri in ["/", "/v14", "/sensors/foo/", "/healthz", "/readyz", "/metrics"] {
+        let request = Request::builder()
+            .method("OPTIONS")
+            .uri(uri)
+            .header("Origin", "https://example.com")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT, "{}", uri);
+        assert!(
+            response.headers().contains_key("Access-Control-Allow-Methods"),
+            "{}",
+            uri
+        );
+    }
+}
+
+#[tokio::test]
+async fn readiness_timeout() {
+    //! Test that the readiness check fails if the store doesn't answer.
+
+    use async_trait::async_trait;
+    use spaceapi_server::store::{Store, StoreError};
+
+    /// Never answers a ping.
+    struct HangingStore;
+
+    #[async_trait]
+    impl Store for HangingStore {
+        async fn get_values(&self, keys: &[&str]) -> Result<Vec<Option<String>>, StoreError> {
+            Ok(vec![None; keys.len()])
+        }
+
+        async fn set_value(&self, _key: &str, _value: &str) -> Result<(), StoreError> {
+            Ok(())
+        }
+
+        async fn ping(&self) -> Result<(), StoreError> {
+            std::future::pending().await
+        }
+    }
+
+    let app: axum::Router = SpaceapiServerBuilder::new(get_status())
+        .store(HangingStore)
+        .build()
+        .unwrap()
+        .into_router();
+    let response = get_json(&app, "/readyz", &[]).await;
+    assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+    assert_eq!(response.body()["checks"]["store"]["status"], "error");
+}
+
+#[tokio::test]
+async fn embedded_router() {
+    //! Test that the routes can be mounted below a prefix in another application.
+
+    #[derive(Clone)]
+    struct PortalState;
+
+    let app: axum::Router = axum::Router::new()
+        .route("/members", axum::routing::get(|| async { "Members" }))
+        .merge(get_server(get_status()).into_router_with_prefix("/spaceapi/"))
+        .with_state(PortalState);
+
+    let call = |method: &str, uri: &str, body: &'static str| {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(Body::from(body))
+            .unwrap();
+        app.clone().oneshot(request)
+    };
+
+    let response = get_json(&app, "/spaceapi", &[]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.body()["space"], "ourspace");
+
+    let response = call("PUT", "/spaceapi/sensors/foo/", "value=42").await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = call("GET", "/spaceapi/healthz", "").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = call("GET", "/members", "").await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = call("GET", "/", "").await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn versioned_routes() {
+    //! Test that the status is served in every supported schema version.
+
+    let app: axum::Router = get_server(get_status()).into_router();
+    let get = |uri: &'static str| {
+        let app = app.clone();
+        async move {
+            let response = get_json(&app, uri, &[]).await;
+            assert_eq!(response.status(), StatusCode::OK);
+            response.into_body()
+        }
+    };
+
+    let v13 = get("/v13").await;
+    assert_eq!(v13["api"], "0.13");
+    assert!(v13.get("api_compatibility").is_none());
+    assert_eq!(
+        v13["issue_report_channels"],
+        serde_json::json!(["email", "twitter"])
+    );
+
+    let v14 = get("/v14").await;
+    assert!(v14.get("api").is_none());
+    assert_eq!(v14["api_compatibility"], serde_json::json!(["14"]));
+    assert!(v14.get("issue_report_channels").is_none());
+    assert_eq!(v14["space"], "ourspace");
+    assert_eq!(v14["state"]["open"], false);
+
+    // An unknown state is null in v0.13 and omitted in v14
+    let mut status = get_status();
+    status.state = Some(api::State::default());
+    let app: axum::Router = get_server(status).into_router();
+    let v13 = get_json(&app, "/v13", &[]).await.into_body();
+    assert_eq!(v13["state"], serde_json::json!({ "open": null }));
+    let v14 = get_json(&app, "/v14", &[]).await.into_body();
+    assert!(v14.get("state").is_none());
+}
+
+#[tokio::test]
+async fn closure_modifiers() {
+    //! Test that closures can be registered as status modifiers.
+
+    use spaceapi_server::modifiers;
+
+    let app: axum::Router = SpaceapiServerBuilder::new(get_status())
+        .redis_connection_info("redis://127.0.0.1/")
+        .add_status_modifier_fn(|status| status.space.push_str(" (beta)"))
+        .add_status_modifier(modifiers::when(
+            |status: &api::Status| status.space.ends_with("(beta)"),
+            |status: &mut api::Status| status.url = "https://beta.example.com/".into(),
+        ))
+        .build()
+        .unwrap()
+        .into_router();
+    let status = get_json(&app, "/", &[]).await.into_body();
+    assert_eq!(status["space"], "ourspace (beta)");
+    assert_eq!(status["url"], "https://beta.example.com/");
+}
+
+#[tokio::test]
+async fn contextual_modifiers() {
+    //! Test that contextual status modifiers see the request.
+
+    use async_trait::async_trait;
+    use spaceapi_server::modifiers::{Context, ContextualStatusModifier, ModifierError};
+
+    struct ShowPath;
+
+    #[async_trait]
+    impl ContextualStatusModifier for ShowPath {
+        async fn modify_with_context(
+            &self,
+            status: &mut api::Status,
+            context: &Context<'_>,
+        ) -> Result<(), ModifierError> {
+            let user_agent = context.headers().get("User-Agent").and_then(|v| v.to_str().ok());
+            status.extensions.insert(
+                "ext_request".into(),
+                serde_json::json!({ "path": context.path(), "user_agent": user_agent }),
+            );
+            Ok(())
+        }
+    }
+
+    let app: axum::Router = SpaceapiServerBuilder::new(get_status())
+        .redis_connection_info("redis://127.0.0.1/")
+        .response_cache_ttl(Duration::from_secs(60))
+        .add_contextual_status_modifier(ShowPath)
+        .build()
+        .unwrap()
+        .into_router();
+    // The modifier depends on the headers, so the status isn't cached
+    for user_agent in ["test", "other"] {
+        let status = get_json(&app, "/v14", &[("User-Agent", user_agent)])
+            .await
+            .into_body();
+        assert_eq!(
+            status["ext_request"],
+            serde_json::json!({ "path": "/v14", "user_agent": user_agent })
+        );
+    }
+}
+
+#[tokio::test]
+async fn modifier_error_policies() {
+    //! Test the error policies of failing status modifiers.
+
+    use async_trait::async_trait;
+    use spaceapi_server::modifiers::{Context, ContextualStatusModifier, ErrorPolicy, ModifierError};
+
+    struct Failing;
+
+    #[async_trait]
+    impl ContextualStatusModifier for Failing {
+        async fn modify_with_context(
+            &self,
+            _: &mut api::Status,
+            _: &Context<'_>,
+        ) -> Result<(), ModifierError> {
+            Err("Calendar unavailable".into())
+        }
+    }
+
+    for (policy, status_code, errors) in [
+        (ErrorPolicy::Skip, StatusCode::OK, None),
+        (
+            ErrorPolicy::Degrade,
+            StatusCode::OK,
+            Some(serde_json::json!(["Calendar unavailable"])),
+        ),
+        (ErrorPolicy::Fail, StatusCode::SERVICE_UNAVAILABLE, None),
+    ] {
+        let app: axum::Router = SpaceapiServerBuilder::new(get_status())
+            .redis_connection_info("redis://127.0.0.1/")
+            .add_contextual_status_modifier_with_policy(Failing, policy)
+            .build()
+            .unwrap()
+            .into_router();
+        let response = get_json(&app, "/", &[]).await;
+        assert_eq!(response.status(), status_code);
+        let body = response.body();
+        if status_code == StatusCode::OK {
+            assert_eq!(body.get("ext_modifier_errors"), errors.as_ref());
+        } else {
+            assert_eq!(body["reason"], "A status modifier failed");
+        }
+    }
+}
+
+#[tokio::test]
+async fn views() {
+    //! Test that clients get the view selected by their address or token.
+
+    use std::net::SocketAddr;
+
+    use axum::extract::ConnectInfo;
+    use spaceapi_server::views::View;
+
+    let builder = |token: &str| {
+        SpaceapiServerBuilder::new(get_status())
+            .redis_connection_info("redis://127.0.0.1/")
+            .response_cache_ttl(Duration::from_secs(60))
+            .add_view(
+                View::new("internal")
+                    .allow_network("10.0.0.0/8".parse().unwrap())
+                    .allow_token(token),
+            )
+            .add_view(View::new("public").hide("contact.email").hide("location.address"))
+    };
+    let server = builder("secret").build().unwrap();
+    let reload_handle = server.reload_handle();
+    let app: axum::Router = server.into_router();
+    let request = |uri: &str, addr: &str, authorization: Option<&str>| {
+        let mut request = Request::builder().uri(uri);
+        if let Some(authorization) = authorization {
+            request = request.header("Authorization", authorization);
+        }
+        let mut request = request.body(Body::empty()).unwrap();
+        let addr: SocketAddr = addr.parse().unwrap();
+        request.extensions_mut().insert(ConnectInfo(addr));
+        request
+    };
+    let check = |cases: Vec<(&'static str, Option<&'static str>, bool)>| {
+        let app = app.clone();
+        async move {
+            for (addr, authorization, internal) in cases {
+                let response = send_json(&app, request("/", addr, authorization)).await;
+                assert_eq!(response.headers()["Vary"], "Authorization");
+                let status = response.body();
+                assert_eq!(status["contact"].get("email").is_some(), internal, "{}", addr);
+                assert_eq!(status["location"].get("address").is_some(), internal, "{}", addr);
+                assert_eq!(status["space"], "ourspace");
+
+                // The metrics export the sensor values
+                let response = app
+                    .clone()
+                    .oneshot(request("/metrics", addr, authorization))
+                    .await
+                    .unwrap();
+                let expected = if internal {
+                    StatusCode::OK
+                } else {
+                    StatusCode::FORBIDDEN
+                };
+                assert_eq!(response.status(), expected, "{}", addr);
+            }
+        }
+    };
+
+    check(vec![
+        ("10.1.2.3:1234", None, true),
+        ("192.0.2.1:1234", None, false),
+        ("192.0.2.1:1234", Some("Bearer secret"), true),
+        ("192.0.2.1:1234", Some("Bearer guess"), false),
+    ])
+    .await;
+
+    // Reloading rotates the tokens
+    reload_handle.reload(builder("rotated")).unwrap();
+    check(vec![
+        ("192.0.2.1:1234", Some("Bearer secret"), false),
+        ("192.0.2.1:1234", Some("Bearer rotated"), true),
+    ])
+    .await;
+
+    // Clients that don't match any view must not get the full status
+    let err = SpaceapiServerBuilder::new(get_status())
+        .redis_connection_info("redis://127.0.0.1/")
+        .add_view(View::new("internal").allow_token("secret"))
+        .build()
+        .err()
+        .unwrap();
+    assert_eq!(
+        err.to_string(),
+        "The last view \"internal\" must apply to all clients, i.e. have no networks and tokens"
+    );
+    assert!(reload_handle
+        .reload(
+            SpaceapiServerBuilder::new(get_status()).add_view(View::new("internal").allow_token("secret"))
+        )
+        .is_err());
+    check(vec![("192.0.2.1:1234", Some("Bearer rotated"), true)]).await;
+}
+
+#[tokio::test]
+async fn metrics() {
+    //! Test that `/metrics` is instrumented and drops sensors removed by a reload.
+
+    use spaceapi_server::api::sensors::TemperatureSensorTemplate;
+    use spaceapi_server::store::MemoryStore;
+
+    let builder = |data_key: &str| {
+        SpaceapiServerBuilder::new(get_status())
+            .store(MemoryStore::new())
+            .add_sensor(
+                TemperatureSensorTemplate {
+                    metadata: Default::default(),
+                    unit: "°C".into(),
+                },
+                data_key.into(),
+            )
+    };
+    let server = builder("temp_room1").build().unwrap();
+    let reload_handle = server.reload_handle();
+    let app = server.into_router();
+    let metrics = || async {
+        let request = Request::builder().uri("/metrics").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    };
+
+    let request = Request::builder()
+        .method("PUT")
+        .uri("/sensors/temp_room1/")
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body(Body::from("value=13.37"))
+        .unwrap();
+    let response = app.clone().oneshot(request).await.unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let output = metrics().await;
+    assert!(output.contains(r#"spaceapi_sensor_value{data_key="temp_room1"} 13.37"#));
+    assert!(output.contains(r#"spaceapi_sensor_updates_total{data_key="temp_room1"} 1"#));
+
+    // Requests to `/metrics` are counted like all others
+    let output = metrics().await;
+    assert!(output.contains(r#"spaceapi_http_requests_total{code="200",route="metrics"} 1"#));
+    assert!(output.contains(r#"spaceapi_http_request_duration_seconds_count{route="metrics"} 1"#));
+
+    // Sensors removed by a reload aren't exported anymore
+    reload_handle.reload(builder("temp_room2")).unwrap();
+    let output = metrics().await;
+    assert!(!output.contains("temp_room1"));
+}
+
+#[tokio::test]
+async fn presence() {
+    //! Test that members can check in and out, and only visible names are served.
+
+    use spaceapi_server::api::sensors::PeopleNowPresentSensorTemplate;
+    use spaceapi_server::events::EventLog;
+    use spaceapi_server::modifiers::RecentEvents;
+    use spaceapi_server::presence::Presence;
+    use spaceapi_server::store::MemoryStore;
+
+    let app = |presence: Presence| -> axum::Router {
+        SpaceapiServerBuilder::new(get_status())
+            .store(MemoryStore::new())
+            .response_cache_ttl(Duration::from_secs(60))
+            .presence(
+                presence
+                    .allow_member("alice", "alice-token")
+                    .allow_member("bob", "bob-token"),
+            )
+            .event_log(EventLog::new())
+            .add_contextual_status_modifier(RecentEvents::new(5))
+            .build()
+            .unwrap()
+            .into_router()
+    };
+    let send = |app: &axum::Router, method: &str, uri: &str, token: &str, body: &'static str| {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("Authorization", format!("Bearer {}", token))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(Body::from(body))
+            .unwrap();
+        app.clone().oneshot(request)
+    };
+
+    let members = app(Presence::new());
+    let people_now_present = || async {
+        let status = get_json(&members, "/", &[]).await.into_body();
+        status["sensors"]["people_now_present"][0].clone()
+    };
+
+    for (uri, token) in [
+        ("/presence/alice/", "guess"),
+        ("/presence/alice/", "bob-token"),
+        ("/presence/eve/", "alice-token"),
+    ] {
+        let response = send(&members, "PUT", uri, token, "visible=true").await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED, "{} {}", uri, token);
+    }
+    let response = send(
+        &members,
+        "PUT",
+        "/presence/alice/",
+        "alice-token",
+        "visible=maybe",
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+    let response = send(&members, "PUT", "/presence/alice/", "alice-token", "visible=true")
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let response = send(&members, "PUT", "/presence/bob/", "bob-token", "")
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(
+        people_now_present().await,
+        serde_json::json!({ "value": 2, "names": ["alice"] })
+    );
+
+    // Members can only check out themselves
+    let response = send(&members, "DELETE", "/presence/alice/", "bob-token", "")
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = send(&members, "DELETE", "/presence/alice/", "alice-token", "")
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(people_now_present().await, serde_json::json!({ "value": 1 }));
+
+    // Automatic checkouts are recorded as well
+    let expiring = app(Presence::new().checkout_after(Duration::ZERO));
+    let response = send(
+        &expiring,
+        "PUT",
+        "/presence/alice/",
+        "alice-token",
+        "visible=true",
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let status = get_json(&expiring, "/", &[]).await.into_body();
+    assert_eq!(status["sensors"]["people_now_present"][0]["value"], 0);
+    let events: Vec<_> = status["events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|event| (event["name"].clone(), event["type"].clone()))
+        .collect();
+    assert_eq!(
+        events,
+        vec![
+            ("alice".into(), "check-out".into()),
+            ("alice".into(), "check-in".into())
+        ]
+    );
+
+    // A registered sensor that can't be read isn't made up from the list
+    let sensor: axum::Router = SpaceapiServerBuilder::new(get_status())
+        .store(MemoryStore::new())
+        .add_sensor(
+            PeopleNowPresentSensorTemplate {
+                metadata: Default::default(),
+            },
+            "people".into(),
+        )
+        .presence(Presence::new().allow_member("alice", "alice-token"))
+        .build()
+        .unwrap()
+        .into_router();
+    let response = send(&sensor, "PUT", "/presence/alice/", "alice-token", "visible=true")
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let status = get_json(&sensor, "/", &[]).await.into_body();
+    assert_eq!(status["sensors"]["people_now_present"], serde_json::Value::Null);
+    let response = send(&sensor, "PUT", "/sensors/people/", "", "value=3")
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    let status = get_json(&sensor, "/", &[]).await.into_body();
+    assert_eq!(
+        status["sensors"]["people_now_present"][0],
+        serde_json::json!({ "value": 3, "names": ["alice"] })
+    );
+}
+
+#[tokio::test]
+async fn events() {
+    //! Test that sensor updates, state changes and posted events are served.
+
+    use spaceapi_server::api::sensors::PeopleNowPresentSensorTemplate;
+    use spaceapi_server::events::EventLog;
+    use spaceapi_server::modifiers::{RecentEvents, StateFromPeopleNowPresent};
+    use spaceapi_server::store::MemoryStore;
+
+    let app: axum::Router = SpaceapiServerBuilder::new(get_status())
+        .store(MemoryStore::new())
+        .add_sensor(
+            PeopleNowPresentSensorTemplate {
+                metadata: Default::default(),
+            },
+            "people".into(),
+        )
+        .add_status_modifier(StateFromPeopleNowPresent)
+        .add_contextual_status_modifier(RecentEvents::new(3))
+        .event_log(EventLog::new().record_sensor("people").allow_token("secret"))
+        .build()
+        .unwrap()
+        .into_router();
+    let send = |method: &str, uri: &str, token: &str, body: &'static str| {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("Authorization", format!("Bearer {}", token))
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body(Body::from(body))
+            .unwrap();
+        app.clone().oneshot(request)
+    };
+    let events = || async {
+        let status = get_json(&app, "/", &[]).await.into_body();
+        let events = status["events"].as_array().cloned().unwrap_or_default();
+        events
+            .iter()
+            .map(|event| format!("{} {} {}", event["name"], event["type"], event["extra"]))
+            .collect::<Vec<_>>()
+    };
+
+    // Reading the status doesn't record state changes
+    assert!(events().await.is_empty());
+
+    // The first update doesn't count as a state change
+    let response = send("PUT", "/sensors/people/", "", "value=3").await.unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(events().await, vec![r#""people" "sensor-update" "3""#]);
+
+    let response = send("POST", "/events", "guess", "name=doorbell&type=ring")
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = send("POST", "/events", "secret", "name=doorbell").await.unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let response = send(
+        "POST",
+        "/events",
+        "secret",
+        "name=doorbell&type=ring&extra=Front+door",
+    )
+    .await
+    .unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(
+        events().await,
+        vec![
+            r#""doorbell" "ring" "Front door""#,
+            r#""people" "sensor-update" "3""#,
+        ]
+    );
+
+    let response = send("PUT", "/sensors/people/", "", "value=0").await.unwrap();
+    assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    assert_eq!(
+        events().await,
+        vec![
+            r#""state" "closed" null"#,
+            r#""people" "sensor-update" "0""#,
+            r#""doorbell" "ring" "Front door""#,
+        ]
+    );
+}
+
+#[tokio::test]
+async fn localized_messages() {
+    //! Test that the messages are rendered in the locale preferred by the client.
+
+    use spaceapi_server::modifiers::StatusModifier;
+
+    struct Greeting;
+
+    impl StatusModifier for Greeting {
+        fn modify(&self, status: &mut api::Status) {
+            self.modify_localized(status, None);
+        }
+
+        fn modify_localized(&self, status: &mut api::Status, locale: Option<&str>) {
+            let message = match locale {
+                Some("de") => "Willkommen",
+                _ => "Welcome",
+            };
+            status.state.get_or_insert_with(Default::default).message = Some(message.into());
+        }
+
+        fn locales(&self) -> Vec<String> {
+            vec!["de".into()]
+        }
+    }
+
+    let app: axum::Router = SpaceapiServerBuilder::new(get_status())
+        .redis_connection_info("redis://127.0.0.1/")
+        .response_cache_ttl(Duration::from_secs(60))
+        .add_status_modifier(Greeting)
+        .build()
+        .unwrap()
+        .into_router();
+    for (accept_language, message, content_language) in [
+        ("de-CH, en;q=0.8", "Willkommen", Some("de")),
+        ("en-US", "Welcome", None),
+        ("en, de;q=0.9", "Welcome", None),
+        ("de", "Willkommen", Some("de")),
+    ] {
+        let response = get_json(&app, "/", &[("Accept-Language", accept_language)]).await;
+        assert_eq!(response.headers()["Vary"], "Accept-Language");
+        assert_eq!(
+            response
+                .headers()
+                .get("Content-Language")
+                .map(|value| value.to_str().unwrap()),
+            content_language
+        );
+        assert_eq!(response.body()["state"]["message"], message);
+    }
+}
+
+#[cfg(feature = "opening-hours")]
+#[tokio::test]
+async fn opening_hours_ignore_static_state() {
+    //! Test that the static state doesn't count as set by sensors.
+
+    use spaceapi_server::modifiers::{NaiveTime, OpeningHours, Precedence, Tz, Weekday};
+
+    // Open around the clock
+    let midnight = NaiveTime::from_hms_opt(0, 0, 0).unwrap();
+    let opening_hours = [
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+        Weekday::Sat,
+        Weekday::Sun,
+    ]
+    .into_iter()
+    .fold(OpeningHours::new(Tz::UTC), |opening_hours, day| {
+        opening_hours.open(day, midnight, midnight)
+    })
+    .precedence(Precedence::Sensors);
+
+    // Other modifiers still see the static state
+    let static_state = |status: &mut api::Status| {
+        let open = status.state.as_ref().and_then(|state| state.open);
+        status.extensions.insert("ext_static_open".into(), open.into());
+    };
+
+    let app: axum::Router = SpaceapiServerBuilder::new(get_status())
+        .redis_connection_info("redis://127.0.0.1/")
+        .add_status_modifier(static_state)
+        .add_contextual_status_modifier(opening_hours)
+        .build()
+        .unwrap()
+        .into_router();
+    let status = get_json(&app, "/", &[]).await.into_body();
+    assert_eq!(status["state"]["open"], true);
+    assert_eq!(status["ext_static_open"], false);
+}
+
+#[cfg(feature = "validation")]
+#[tokio::test]
+async fn strict_validation() {
+    //! Test that an invalid status is rejected in strict validation mode.
+
+    use spaceapi_server::modifiers::StatusModifier;
+    use spaceapi_server::ValidationMode;
+
+    /// Removes the opening state, which is required in v0.13.
+    struct RemoveState;
+
+    impl StatusModifier for RemoveState {
+        fn modify(&self, status: &mut api::Status) {
+            status.state = None;
+        }
+    }
+
+    let builder = SpaceapiServerBuilder::new(get_status())
+        .redis_connection_info("redis://127.0.0.1/")
+        .validation(ValidationMode::Strict);
+    builder.validate().unwrap();
+    let app: axum::Router = builder
+        .add_status_modifier(RemoveState)
+        .build()
+        .unwrap()
+        .into_router();
+
+    let response = get_json(&app, "/", &[]).await;
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(
+        response.into_body(),
+        serde_json::json!({"status": "error", "reason": "Status does not match the SpaceAPI schema"})
+    );
+
+    // The state is optional in v14
+    let response = get_json(&app, "/v14", &[]).await;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    // The status is validated after the view removed fields
+    let app: axum::Router = SpaceapiServerBuilder::new(get_status())
+        .redis_connection_info("redis://127.0.0.1/")
+        .validation(ValidationMode::Strict)
+        .add_view(spaceapi_server::views::View::new("public").hide("location"))
+        .build()
+        .unwrap()
+        .into_router();
+    let response = get_json(&app, "/v14", &[]).await;
+    assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+}
+
+#[cfg(feature = "tls")]
+#[tokio::test]
+async fn server_serves_tls() {
+    //! Test that the spaceapi server serves HTTPS with the specified certificate.
+
+    use std::sync::Arc;
+    use tokio_rustls::rustls::pki_types::ServerName;
+    use tokio_rustls::rustls::{crypto, ClientConfig, RootCertStore};
+    use tokio_rustls::TlsConnector;
+
+    // Ip / port for test server
+    let ip = Ipv4Addr::new(127, 0, 0, 1);
+    let port = 3347;
+
+    // Create a self-signed certificate
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".into()]).unwrap();
+    let dir = std::env::temp_dir();
+    let cert_path = dir.join(format!("spaceapi-server-tls-{}.crt", std::process::id()));
+    let key_path = dir.join(format!("spaceapi-server-tls-{}.key", std::process::id()));
+    std::fs::write(&cert_path, cert.cert.pem()).unwrap();
+    std::fs::write(&key_path, cert.key_pair.serialize_pem()).unwrap();
+
+    // Instantiate and start server
+    let server = get_server(get_status());
+    let shutdown_handle = server.shutdown_handle();
+    let serving = tokio::spawn(server.serve_tls((ip, port), cert_path.clone(), key_path.clone()));
+    wait_for_server(ip, port).await;
+
+    // Connect with a client trusting the certificate
+    let mut roots = RootCertStore::empty();
+    roots.add(cert.cert.der().clone()).unwrap();
+    let config = ClientConfig::builder_with_provider(Arc::new(crypto::ring::default_provider()))
+        .with_safe_default_protocol_versions()
+        .unwrap()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let stream = tokio::net::TcpStream::connect((ip, port)).await.unwrap();
+    let mut stream = TlsConnector::from(Arc::new(config))
+        .connect(ServerName::try_from("localhost").unwrap(), stream)
+        .await
+        .unwrap();
+    stream
+        .write_all(b"GET /healthz HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = Vec::new();
+    // Servers may close TLS connections without a close_notify alert
+    let _ = stream.read_to_end(&mut response).await;
+    let response = String::from_utf8(response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with(r#"{"status":"ok"}"#));
 
     // Close server
-    listening.close().unwrap();
+    assert!(shutdown_handle.shutdown(Duration::from_secs(5)).await);
+    serving.await.unwrap().unwrap();
+    std::fs::remove_file(cert_path).unwrap();
+    std::fs::remove_file(key_path).unwrap();
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn server_serves_unix_socket() {
+    //! Test that the spaceapi server listens on a Unix domain socket.
+
+    let path = std::env::temp_dir().join(format!("spaceapi-server-{}.sock", std::process::id()));
+
+    // Instantiate and start server
+    let server = get_server(get_status());
+    let shutdown_handle = server.shutdown_handle();
+    let serving = tokio::spawn(server.serve_unix(path.clone()));
+
+    // Connect
+    let mut stream = None;
+    for _ in 0..100 {
+        if let Ok(s) = tokio::net::UnixStream::connect(&path).await {
+            stream = Some(s);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+    let mut stream = stream.expect("Server did not start");
+    stream
+        .write_all(b"GET /healthz HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with(r#"{"status":"ok"}"#));
+
+    // Close server, the socket file is removed
+    assert!(shutdown_handle.shutdown(Duration::from_secs(5)).await);
+    serving.await.unwrap().unwrap();
+    assert!(!path.exists());
 }